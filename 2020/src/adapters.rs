@@ -0,0 +1,248 @@
+use std::collections::BTreeMap;
+use std::error::Error;
+
+use serde::Serialize;
+
+use crate::count::{checked_add, Count};
+
+/// Which steps up in joltage an adapter can take, and how far above the highest adapter the
+/// device's built-in adapter sits.
+#[derive(Clone, Debug)]
+pub struct JoltageRules {
+    steps: Vec<i32>,
+    device_offset: i32,
+}
+
+impl Default for JoltageRules {
+    fn default() -> Self {
+        JoltageRules {
+            steps: vec![1, 2, 3],
+            device_offset: 3,
+        }
+    }
+}
+
+impl JoltageRules {
+    pub fn new(steps: &[i32], device_offset: i32) -> Result<JoltageRules, Box<dyn Error>> {
+        if steps.is_empty() || steps.iter().any(|&s| s <= 0) {
+            return Err(format!("adapter steps must be positive, got {:?}", steps).into());
+        }
+        if device_offset <= 0 {
+            return Err(format!("device offset must be positive, got {}", device_offset).into());
+        }
+        let mut steps = steps.to_vec();
+        steps.sort_unstable();
+        steps.dedup();
+        Ok(JoltageRules {
+            steps,
+            device_offset,
+        })
+    }
+
+    pub fn allows(&self, step: i32) -> bool {
+        self.steps.binary_search(&step).is_ok()
+    }
+
+    fn max_step(&self) -> i32 {
+        *self.steps.last().unwrap()
+    }
+
+    /// The outlet, every adapter in increasing order, then the device.
+    pub fn full_chain(&self, adapters: &[i32]) -> Vec<i32> {
+        let mut chain = Vec::with_capacity(adapters.len() + 2);
+        chain.push(0);
+        chain.extend_from_slice(adapters);
+        chain.sort_unstable();
+        chain.push(chain.last().unwrap() + self.device_offset);
+        chain
+    }
+
+    /// Makes sure every adapter can be plugged into the one before it, which is what using
+    /// all of them at once needs.
+    pub fn validate_chain(&self, chain: &[i32]) -> Result<(), Box<dyn Error>> {
+        for pair in chain.windows(2) {
+            let gap = pair[1] - pair[0];
+            if !self.allows(gap) {
+                let steps: Vec<String> = self.steps.iter().map(|s| s.to_string()).collect();
+                return Err(format!(
+                    "no adapter can go from {} to {} jolts: a step of {} isn't one of {}",
+                    pair[0],
+                    pair[1],
+                    gap,
+                    steps.join(", ")
+                )
+                .into());
+            }
+        }
+        Ok(())
+    }
+}
+
+/// How many times each step in joltage comes up when every adapter is used.
+pub fn calc_joltage_diffs(
+    chain: &[i32],
+    rules: &JoltageRules,
+) -> Result<BTreeMap<i32, i32>, Box<dyn Error>> {
+    rules.validate_chain(chain)?;
+
+    let mut diffs = BTreeMap::new();
+    for pair in chain.windows(2) {
+        *diffs.entry(pair[1] - pair[0]).or_insert(0) += 1;
+    }
+    Ok(diffs)
+}
+
+/// How many ways there are to get from the outlet to the device along `chain`. When there
+/// are none, fails like `calc_joltage_diffs` with the gap that can't be bridged.
+///
+/// The number of ways to reach a joltage is the sum of the ways to reach each earlier one it
+/// can be plugged into, so one pass over the chain is enough.
+pub fn count_arrangements(chain: &[i32], rules: &JoltageRules) -> Result<Count, Box<dyn Error>> {
+    let mut ways: Vec<Count> = Vec::with_capacity(chain.len());
+    for (i, &joltage) in chain.iter().enumerate() {
+        if i == 0 {
+            ways.push(Count::from(1u64));
+            continue;
+        }
+        let mut total = Count::from(0u64);
+        for prev in (0..i).rev() {
+            let step = joltage - chain[prev];
+            if step > rules.max_step() {
+                break;
+            }
+            if rules.allows(step) {
+                total = checked_add(&total, &ways[prev], "the number of adapter arrangements")?;
+            }
+        }
+        ways.push(total);
+    }
+    let total = ways.pop().unwrap_or_else(|| Count::from(0u64));
+    // with steps that skip sizes an adapter can sometimes be jumped over, so only a chain no
+    // arrangement gets through is an error, and it has to have a gap no step covers
+    if total == Count::from(0u64) {
+        rules.validate_chain(chain)?;
+    }
+    Ok(total)
+}
+
+/// Lists the arrangements themselves, each as the adapters used in order, stopping after
+/// `limit` of them. Only practical for small inputs, to check `count_arrangements` against.
+pub fn list_arrangements(chain: &[i32], rules: &JoltageRules, limit: usize) -> Vec<Vec<i32>> {
+    let mut arrangements = Vec::new();
+    let mut used = Vec::new();
+    collect_arrangements(chain, rules, 0, &mut used, &mut arrangements, limit);
+    arrangements
+}
+
+fn collect_arrangements(
+    chain: &[i32],
+    rules: &JoltageRules,
+    from: usize,
+    used: &mut Vec<i32>,
+    arrangements: &mut Vec<Vec<i32>>,
+    limit: usize,
+) {
+    let device = chain.len() - 1;
+    for next in from + 1..chain.len() {
+        let step = chain[next] - chain[from];
+        if arrangements.len() >= limit || step > rules.max_step() {
+            return;
+        }
+        if !rules.allows(step) {
+            continue;
+        }
+        if next == device {
+            arrangements.push(used.clone());
+        } else {
+            used.push(chain[next]);
+            collect_arrangements(chain, rules, next, used, arrangements, limit);
+            used.pop();
+        }
+    }
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum LinkKind {
+    Outlet,
+    Adapter,
+    Device,
+}
+
+/// One joltage along the chain, with the step up from the joltage before it.
+#[derive(Serialize)]
+pub struct Link {
+    pub joltage: i32,
+    pub kind: LinkKind,
+    pub step: Option<i32>,
+    pub mandatory: bool,
+}
+
+#[derive(Serialize)]
+pub struct ChainReport {
+    /// How many times each step comes up, keyed by step size.
+    pub histogram: BTreeMap<i32, i32>,
+    pub chain: Vec<Link>,
+    /// Adapters that every arrangement has to use.
+    pub mandatory: Vec<i32>,
+}
+
+/// Describes the chain that uses every adapter: the steps it takes, and which adapters can't
+/// be left out of any arrangement.
+pub fn chain_report(chain: &[i32], rules: &JoltageRules) -> Result<ChainReport, Box<dyn Error>> {
+    let histogram = calc_joltage_diffs(chain, rules)?;
+    let mandatory_at = find_mandatory(chain, rules);
+
+    let last = chain.len() - 1;
+    let links = chain
+        .iter()
+        .enumerate()
+        .map(|(i, &joltage)| Link {
+            joltage,
+            kind: match i {
+                0 => LinkKind::Outlet,
+                i if i == last => LinkKind::Device,
+                _ => LinkKind::Adapter,
+            },
+            step: if i == 0 {
+                None
+            } else {
+                Some(joltage - chain[i - 1])
+            },
+            mandatory: mandatory_at[i],
+        })
+        .collect();
+    let mandatory = (1..last)
+        .filter(|&i| mandatory_at[i])
+        .map(|i| chain[i])
+        .collect();
+
+    Ok(ChainReport {
+        histogram,
+        chain: links,
+        mandatory,
+    })
+}
+
+/// Since the chain is valid every joltage can be reached from the outlet and can reach the
+/// device, so an adapter can be dropped exactly when some allowed step jumps right over it.
+fn find_mandatory(chain: &[i32], rules: &JoltageRules) -> Vec<bool> {
+    let mut mandatory = vec![true; chain.len()];
+    // the furthest any earlier joltage can jump to
+    let mut furthest = 0;
+    for i in 0..chain.len() {
+        if furthest > i {
+            mandatory[i] = false;
+        }
+        for next in i + 1..chain.len() {
+            let step = chain[next] - chain[i];
+            if step > rules.max_step() {
+                break;
+            }
+            if rules.allows(step) {
+                furthest = furthest.max(next);
+            }
+        }
+    }
+    mandatory
+}
@@ -0,0 +1,516 @@
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::error::Error;
+use std::fmt::{self, Write};
+
+use serde::Serialize;
+
+use crate::count::{checked_add, checked_mul, Count};
+use crate::graph::{EdgeDirection, Graph, NodeId};
+
+/// Bag rules as a graph: an edge `a -> b` weighted `n` means every `a` bag holds `n` `b` bags.
+pub type BagRules = Graph<u64>;
+
+#[derive(Debug)]
+pub struct BagRuleError {
+    line: usize,
+    column: usize,
+    message: String,
+}
+
+impl fmt::Display for BagRuleError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "bag rule on line {}, column {}: {}",
+            self.line, self.column, self.message
+        )
+    }
+}
+
+impl Error for BagRuleError {}
+
+/// One line of the rules: a bag color and what each bag of that color holds.
+#[derive(Debug, PartialEq)]
+pub struct BagRule {
+    pub color: String,
+    pub contents: Vec<(u64, String)>,
+}
+
+/// Parses a single rule according to
+///
+/// ```text
+/// rule     = color bag "contain" contents "."
+/// contents = "no" "other" "bags" | item { "," item }
+/// item     = number color bag
+/// color    = word { word }
+/// bag      = "bag" | "bags"
+/// ```
+///
+/// where words are separated by any amount of whitespace.
+pub fn parse_bag_rule(line_num: usize, line: &str) -> Result<BagRule, BagRuleError> {
+    let mut parser = RuleParser::new(line_num, line);
+
+    let color = parser.color()?;
+    parser.bag()?;
+    parser.expect("contain")?;
+
+    let mut contents = Vec::new();
+    if parser.peek() == Some("no") {
+        parser.expect("no")?;
+        parser.expect("other")?;
+        parser.expect("bags")?;
+    } else {
+        loop {
+            let count = parser.number()?;
+            let inner = parser.color()?;
+            parser.bag()?;
+            contents.push((count, inner));
+            if parser.peek() != Some(",") {
+                break;
+            }
+            parser.expect(",")?;
+        }
+    }
+    parser.expect(".")?;
+    parser.end()?;
+
+    Ok(BagRule { color, contents })
+}
+
+/// Splits a line into words, numbers, commas and periods, remembering the column (in
+/// characters) each one starts at, and walks through them one grammar rule at a time.
+struct RuleParser<'a> {
+    line: usize,
+    tokens: Vec<(usize, &'a str)>,
+    pos: usize,
+    end_column: usize,
+}
+
+impl<'a> RuleParser<'a> {
+    fn new(line: usize, text: &'a str) -> RuleParser<'a> {
+        let mut tokens = Vec::new();
+        // byte offset and column of the token being read
+        let mut start = None;
+        for (column, (i, c)) in (1..).zip(text.char_indices()) {
+            let is_punctuation = c == ',' || c == '.';
+            if c.is_whitespace() || is_punctuation {
+                if let Some((s, s_column)) = start.take() {
+                    tokens.push((s_column, &text[s..i]));
+                }
+                if is_punctuation {
+                    tokens.push((column, &text[i..i + 1]));
+                }
+            } else if start.is_none() {
+                start = Some((i, column));
+            }
+        }
+        if let Some((s, s_column)) = start {
+            tokens.push((s_column, &text[s..]));
+        }
+
+        RuleParser {
+            line,
+            tokens,
+            pos: 0,
+            end_column: text.trim_end().chars().count() + 1,
+        }
+    }
+
+    fn peek(&self) -> Option<&'a str> {
+        self.tokens.get(self.pos).map(|&(_, t)| t)
+    }
+
+    fn error(&self, expected: &str) -> BagRuleError {
+        let (column, found) = match self.tokens.get(self.pos) {
+            Some(&(column, token)) => (column, format!("{:?}", token)),
+            None => (self.end_column, String::from("end of line")),
+        };
+        BagRuleError {
+            line: self.line,
+            column,
+            message: format!("expected {}, found {}", expected, found),
+        }
+    }
+
+    fn expect(&mut self, token: &str) -> Result<(), BagRuleError> {
+        if self.peek() == Some(token) {
+            self.pos += 1;
+            Ok(())
+        } else {
+            Err(self.error(&format!("{:?}", token)))
+        }
+    }
+
+    fn number(&mut self) -> Result<u64, BagRuleError> {
+        match self.peek().and_then(|t| t.parse().ok()) {
+            Some(n) => {
+                self.pos += 1;
+                Ok(n)
+            }
+            None => Err(self.error("a number of bags")),
+        }
+    }
+
+    fn bag(&mut self) -> Result<(), BagRuleError> {
+        match self.peek() {
+            Some("bag") | Some("bags") => {
+                self.pos += 1;
+                Ok(())
+            }
+            _ => Err(self.error("\"bag\" or \"bags\"")),
+        }
+    }
+
+    fn color(&mut self) -> Result<String, BagRuleError> {
+        let mut words = Vec::new();
+        while let Some(word) = self.peek() {
+            if word == "bag" || word == "bags" || !word.chars().all(char::is_alphabetic) {
+                break;
+            }
+            words.push(word);
+            self.pos += 1;
+        }
+        if words.is_empty() {
+            Err(self.error("a bag color"))
+        } else {
+            Ok(words.join(" "))
+        }
+    }
+
+    fn end(&self) -> Result<(), BagRuleError> {
+        match self.peek() {
+            None => Ok(()),
+            Some(_) => Err(self.error("end of line")),
+        }
+    }
+}
+
+pub fn parse_bag_rules(lines: &[String]) -> Result<BagRules, Box<dyn Error>> {
+    let mut contains = Graph::new();
+    let mut defined_on: HashMap<String, usize> = HashMap::new();
+
+    for (i, line) in lines.iter().enumerate() {
+        if line.trim().is_empty() {
+            continue;
+        }
+        let rule = parse_bag_rule(i + 1, line)?;
+        if let Some(first) = defined_on.insert(rule.color.clone(), i + 1) {
+            return Err(BagRuleError {
+                line: i + 1,
+                column: 1,
+                message: format!("{} bags already have a rule on line {}", rule.color, first),
+            }
+            .into());
+        }
+
+        contains.add_node(&rule.color);
+        for (count, inner) in &rule.contents {
+            contains.add_edge(&rule.color, inner, *count);
+        }
+    }
+    Ok(contains)
+}
+
+pub fn find_bag(graph: &BagRules, color: &str) -> Result<NodeId, Box<dyn Error>> {
+    graph
+        .id(color)
+        .ok_or_else(|| format!("no rule mentions {} bags", color).into())
+}
+
+pub fn format_chain(graph: &BagRules, chain: &[NodeId]) -> String {
+    let names: Vec<&str> = chain.iter().map(|&n| graph.name(n)).collect();
+    names.join(" -> ")
+}
+
+fn cycle_error(graph: &BagRules, cycle: &[NodeId]) -> String {
+    let mut chain = cycle.to_vec();
+    chain.push(cycle[0]);
+    format!("bag rules contain a cycle: {}", format_chain(graph, &chain))
+}
+
+pub fn count_reachable_nodes(start: &str, graph: &BagRules) -> usize {
+    match graph.id(start) {
+        Some(start) => graph.reachable(start, EdgeDirection::Reverse).len(),
+        None => 0,
+    }
+}
+
+/// Counts the bags nested inside other bags. Each bag's total is computed once and reused by
+/// every bag that holds it, so shared sub-hierarchies are only walked the first time.
+pub struct InnerBagCounter<'a> {
+    graph: &'a BagRules,
+    totals: HashMap<NodeId, Count>,
+}
+
+impl<'a> InnerBagCounter<'a> {
+    pub fn new(graph: &'a BagRules) -> InnerBagCounter<'a> {
+        InnerBagCounter {
+            graph,
+            totals: HashMap::new(),
+        }
+    }
+
+    pub fn count(&mut self, start: NodeId) -> Result<Count, Box<dyn Error>> {
+        if let Some(total) = self.totals.get(&start) {
+            return Ok(total.to_owned());
+        }
+        self.count_from(&[start])?;
+        Ok(self.totals[&start].to_owned())
+    }
+
+    /// Computes the total for every bag with a single sort of the whole graph, so that
+    /// counting each bag afterwards is just a lookup.
+    pub fn count_all(&mut self) -> Result<(), Box<dyn Error>> {
+        let roots: Vec<NodeId> = self.graph.nodes().collect();
+        self.count_from(&roots)
+    }
+
+    fn count_from(&mut self, roots: &[NodeId]) -> Result<(), Box<dyn Error>> {
+        let totals = &self.totals;
+        let order = self
+            .graph
+            .topological_sort_until(roots, |bag| totals.contains_key(&bag))
+            .map_err(|cycle| cycle_error(self.graph, &cycle))?;
+
+        // innermost bags come last, so walking backwards every nested total is already known
+        for &bag in order.iter().rev() {
+            let mut total = Count::from(0u64);
+            for (inner, c) in self.graph.successors(bag) {
+                let what = format!("the number of bags inside {}", self.graph.name(bag));
+                let with_inner = checked_add(&self.totals[inner], &Count::from(1u64), &what)?;
+                let nested = checked_mul(&with_inner, &Count::from(*c), &what)?;
+                total = checked_add(&total, &nested, &what)?;
+            }
+            self.totals.insert(bag, total);
+        }
+        Ok(())
+    }
+}
+
+pub fn count_inner_bags(start: &str, graph: &BagRules) -> Result<Count, Box<dyn Error>> {
+    match graph.id(start) {
+        Some(start) => InnerBagCounter::new(graph).count(start),
+        None => Ok(Count::from(0u64)),
+    }
+}
+
+/// Ways `target` can end up inside another bag, each listed from the outermost bag in,
+/// stopping after `limit` of them. The number of paths grows exponentially with the depth of
+/// the rules, so this is for spot checks rather than listing them all.
+pub fn containment_paths(graph: &BagRules, target: NodeId, limit: usize) -> Vec<Vec<NodeId>> {
+    let mut paths = Vec::new();
+    let mut path = vec![target];
+    collect_containment_paths(graph, &mut path, &mut paths, limit);
+    for p in &mut paths {
+        p.reverse();
+    }
+    paths.sort();
+    paths
+}
+
+fn collect_containment_paths(
+    graph: &BagRules,
+    path: &mut Vec<NodeId>,
+    paths: &mut Vec<Vec<NodeId>>,
+    limit: usize,
+) {
+    let inner = *path.last().unwrap();
+    for &(outer, _) in graph.predecessors(inner) {
+        if paths.len() >= limit {
+            return;
+        }
+        // a cyclic rule set would otherwise go round forever
+        if path.contains(&outer) {
+            continue;
+        }
+        path.push(outer);
+        paths.push(path.clone());
+        collect_containment_paths(graph, path, paths, limit);
+        path.pop();
+    }
+}
+
+/// The longest run of bags each directly inside the one before, starting from `start` if one
+/// is given and from anywhere otherwise.
+pub fn deepest_chain(
+    graph: &BagRules,
+    start: Option<NodeId>,
+) -> Result<Vec<NodeId>, Box<dyn Error>> {
+    let roots: Vec<NodeId> = match start {
+        Some(start) => vec![start],
+        None => graph.nodes().collect(),
+    };
+    let order = graph
+        .topological_sort_from(&roots)
+        .map_err(|cycle| cycle_error(graph, &cycle))?;
+
+    let mut depth: HashMap<NodeId, usize> = HashMap::new();
+    let mut next: HashMap<NodeId, NodeId> = HashMap::new();
+    for &bag in order.iter().rev() {
+        let deepest_inner = graph
+            .successors(bag)
+            .iter()
+            .map(|&(inner, _)| inner)
+            .max_by_key(|inner| depth[inner]);
+        match deepest_inner {
+            Some(inner) => {
+                depth.insert(bag, depth[&inner] + 1);
+                next.insert(bag, inner);
+            }
+            None => {
+                depth.insert(bag, 0);
+            }
+        }
+    }
+
+    let mut cur = match start {
+        Some(start) => start,
+        None => match roots.iter().max_by_key(|&r| depth[r]) {
+            Some(&r) => r,
+            None => return Ok(Vec::new()),
+        },
+    };
+    let mut chain = vec![cur];
+    while let Some(&inner) = next.get(&cur) {
+        chain.push(inner);
+        cur = inner;
+    }
+    Ok(chain)
+}
+
+/// The bag holding the most bags in total, counting everything nested inside it.
+pub fn largest_bag(graph: &BagRules) -> Result<Option<(NodeId, Count)>, Box<dyn Error>> {
+    let mut counter = InnerBagCounter::new(graph);
+    counter.count_all()?;
+    let mut largest: Option<(NodeId, Count)> = None;
+    for bag in graph.nodes() {
+        let total = counter.count(bag)?;
+        if largest.as_ref().is_none_or(|(_, most)| total > *most) {
+            largest = Some((bag, total));
+        }
+    }
+    Ok(largest)
+}
+
+/// Renders the rules as a Graphviz digraph, with each edge labelled by how many bags it holds.
+/// When a root is given it is drawn in gold and everything it holds, directly or not, is
+/// filled in.
+pub fn to_dot(graph: &BagRules, root: Option<NodeId>) -> String {
+    let held: HashSet<NodeId> = match root {
+        Some(root) => graph.reachable(root, EdgeDirection::Forward),
+        None => HashSet::new(),
+    };
+
+    let mut dot = String::from("digraph bags {\n");
+    for bag in graph.nodes() {
+        let style = if Some(bag) == root {
+            " [style=filled, fillcolor=gold]"
+        } else if held.contains(&bag) {
+            " [style=filled, fillcolor=lightblue]"
+        } else {
+            ""
+        };
+        writeln!(dot, "    {:?}{};", graph.name(bag), style).unwrap();
+    }
+    for bag in graph.nodes() {
+        for &(inner, count) in graph.successors(bag) {
+            let highlight = if Some(bag) == root || held.contains(&bag) {
+                ", color=blue"
+            } else {
+                ""
+            };
+            writeln!(
+                dot,
+                "    {:?} -> {:?} [label={}{}];",
+                graph.name(bag),
+                graph.name(inner),
+                count,
+                highlight
+            )
+            .unwrap();
+        }
+    }
+    dot.push_str("}\n");
+    dot
+}
+
+#[derive(Serialize)]
+struct BagContent<'a> {
+    color: &'a str,
+    count: u64,
+}
+
+/// Renders the rules as a JSON object mapping each color to the bags it directly holds.
+pub fn to_json(graph: &BagRules) -> Result<String, Box<dyn Error>> {
+    let adjacency: BTreeMap<&str, Vec<BagContent>> = graph
+        .nodes()
+        .map(|bag| {
+            let contents = graph
+                .successors(bag)
+                .iter()
+                .map(|&(inner, count)| BagContent {
+                    color: graph.name(inner),
+                    count,
+                })
+                .collect();
+            (graph.name(bag), contents)
+        })
+        .collect();
+    Ok(serde_json::to_string_pretty(&adjacency)?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn error_column(line: &str) -> (usize, String) {
+        let err = parse_bag_rule(1, line).unwrap_err();
+        (err.column, err.message)
+    }
+
+    #[test]
+    fn parses_rules() {
+        let rule = parse_bag_rule(
+            1,
+            "light red bags contain 1 bright white bag, 2 muted yellow bags.",
+        );
+        assert_eq!(
+            rule.unwrap(),
+            BagRule {
+                color: String::from("light red"),
+                contents: vec![
+                    (1, String::from("bright white")),
+                    (2, String::from("muted yellow")),
+                ],
+            }
+        );
+    }
+
+    #[test]
+    fn columns_point_at_the_token() {
+        assert_eq!(
+            error_column("dark orange bags contain x bags."),
+            (26, String::from("expected a number of bags, found \"x\""))
+        );
+        assert_eq!(
+            error_column("dark orange bags contain no other bags"),
+            (39, String::from("expected \".\", found end of line"))
+        );
+    }
+
+    #[test]
+    fn columns_count_characters_not_bytes() {
+        assert_eq!(
+            error_column("dark ōrange bags contain x bags."),
+            (26, String::from("expected a number of bags, found \"x\""))
+        );
+        assert_eq!(
+            error_column("dark ōrange bags contain no other bags"),
+            (39, String::from("expected \".\", found end of line"))
+        );
+        assert_eq!(
+            error_column("dark ōrange bags contain 1 pale blue bag,, 2 red bags."),
+            (42, String::from("expected a number of bags, found \",\""))
+        );
+    }
+}
@@ -0,0 +1,50 @@
+use std::error::Error;
+use std::fmt;
+
+/// Answers that count things, and so can get very big very quickly. By default they're `u64`
+/// and every operation is checked; building with the `bigint` feature makes them exact.
+#[cfg(not(feature = "bigint"))]
+pub type Count = u64;
+#[cfg(feature = "bigint")]
+pub type Count = num_bigint::BigUint;
+
+#[derive(Debug)]
+pub struct OverflowError {
+    what: String,
+}
+
+impl fmt::Display for OverflowError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "{} overflowed u64; build with `--features bigint` for an exact answer",
+            self.what
+        )
+    }
+}
+
+impl Error for OverflowError {}
+
+#[cfg(not(feature = "bigint"))]
+pub fn checked_add(a: &Count, b: &Count, what: &str) -> Result<Count, OverflowError> {
+    a.checked_add(*b).ok_or_else(|| OverflowError {
+        what: String::from(what),
+    })
+}
+
+#[cfg(not(feature = "bigint"))]
+pub fn checked_mul(a: &Count, b: &Count, what: &str) -> Result<Count, OverflowError> {
+    a.checked_mul(*b).ok_or_else(|| OverflowError {
+        what: String::from(what),
+    })
+}
+
+#[cfg(feature = "bigint")]
+pub fn checked_add(a: &Count, b: &Count, _what: &str) -> Result<Count, OverflowError> {
+    Ok(a + b)
+}
+
+#[cfg(feature = "bigint")]
+pub fn checked_mul(a: &Count, b: &Count, _what: &str) -> Result<Count, OverflowError> {
+    Ok(a * b)
+}
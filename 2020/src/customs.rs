@@ -0,0 +1,259 @@
+use std::collections::BTreeMap;
+use std::fmt;
+use std::ops::{BitAnd, BitOr, BitXor};
+
+use crate::records::{FromRecord, Record, RecordError};
+
+/// The questions `a` to `z` someone answered "yes" to, one bit per question.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub struct Answers(u32);
+
+impl Answers {
+    pub const ALL: Answers = Answers((1 << 26) - 1);
+
+    pub fn len(self) -> usize {
+        self.0.count_ones() as usize
+    }
+
+    pub fn contains(self, question: char) -> bool {
+        question_bit(question).is_some_and(|bit| self.0 & bit != 0)
+    }
+
+    /// The questions in the set, in order.
+    pub fn questions(self) -> impl Iterator<Item = char> {
+        questions().filter(move |&q| self.contains(q))
+    }
+}
+
+/// Every question, `a` to `z`.
+pub fn questions() -> impl Iterator<Item = char> {
+    'a'..='z'
+}
+
+fn question_bit(question: char) -> Option<u32> {
+    if question.is_ascii_lowercase() {
+        Some(1 << (question as u32 - 'a' as u32))
+    } else {
+        None
+    }
+}
+
+impl BitOr for Answers {
+    type Output = Answers;
+
+    fn bitor(self, other: Answers) -> Answers {
+        Answers(self.0 | other.0)
+    }
+}
+
+impl BitAnd for Answers {
+    type Output = Answers;
+
+    fn bitand(self, other: Answers) -> Answers {
+        Answers(self.0 & other.0)
+    }
+}
+
+impl BitXor for Answers {
+    type Output = Answers;
+
+    fn bitxor(self, other: Answers) -> Answers {
+        Answers(self.0 ^ other.0)
+    }
+}
+
+impl fmt::Display for Answers {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        self.questions().try_for_each(|q| write!(f, "{}", q))
+    }
+}
+
+/// The answers of a group of people travelling together, one person per line.
+#[derive(Debug)]
+pub struct Group {
+    /// The line in the input the group starts on.
+    pub line: usize,
+    pub people: Vec<Answers>,
+}
+
+impl FromRecord for Group {
+    fn from_record(record: &Record) -> Result<Self, RecordError> {
+        let mut people = Vec::new();
+        for (i, line) in record.lines.iter().enumerate() {
+            let mut answers = Answers::default();
+            for (column, c) in line.trim_end().chars().enumerate() {
+                match question_bit(c) {
+                    Some(bit) => answers.0 |= bit,
+                    None => {
+                        return Err(RecordError {
+                            line: record.line + i,
+                            column: column + 1,
+                            message: format!("expected a question from a to z, found {:?}", c),
+                        })
+                    }
+                }
+            }
+            people.push(answers);
+        }
+        Ok(Group {
+            line: record.line,
+            people,
+        })
+    }
+}
+
+impl Group {
+    pub fn size(&self) -> usize {
+        self.people.len()
+    }
+
+    /// The questions anyone in the group answered.
+    pub fn union(&self) -> Answers {
+        self.people
+            .iter()
+            .fold(Answers::default(), |acc, &a| acc | a)
+    }
+
+    /// The questions everyone in the group answered.
+    pub fn intersection(&self) -> Answers {
+        self.people.iter().fold(Answers::ALL, |acc, &a| acc & a)
+    }
+
+    /// The questions an odd number of people in the group answered, which for two people is
+    /// the questions only one of them answered.
+    pub fn symmetric_difference(&self) -> Answers {
+        self.people
+            .iter()
+            .fold(Answers::default(), |acc, &a| acc ^ a)
+    }
+
+    /// How many people answered each question, `a` first.
+    pub fn question_counts(&self) -> [usize; 26] {
+        let mut counts = [0; 26];
+        for (i, q) in questions().enumerate() {
+            counts[i] = self.people.iter().filter(|a| a.contains(q)).count();
+        }
+        counts
+    }
+
+    /// The questions at least `k` people in the group answered. `at_least(1)` is the union
+    /// and `at_least(size)` the intersection.
+    pub fn at_least(&self, k: usize) -> Answers {
+        let counts = self.question_counts();
+        questions()
+            .zip(counts.iter())
+            .filter(|&(_, &count)| count >= k.max(1))
+            .fold(Answers::default(), |acc, (q, _)| {
+                acc | Answers(question_bit(q).unwrap())
+            })
+    }
+}
+
+/// How one question was answered across every group.
+#[derive(Debug)]
+pub struct QuestionStats {
+    pub question: char,
+    /// People who answered it.
+    pub people: usize,
+    /// Groups where at least one person answered it.
+    pub groups_any: usize,
+    /// Groups where everyone answered it.
+    pub groups_all: usize,
+}
+
+pub fn question_stats(groups: &[Group]) -> Vec<QuestionStats> {
+    questions()
+        .enumerate()
+        .map(|(i, question)| QuestionStats {
+            question,
+            people: groups.iter().map(|g| g.question_counts()[i]).sum(),
+            groups_any: groups
+                .iter()
+                .filter(|g| g.union().contains(question))
+                .count(),
+            groups_all: groups
+                .iter()
+                .filter(|g| g.intersection().contains(question))
+                .count(),
+        })
+        .collect()
+}
+
+/// How often a question was answered by people in groups of one size.
+#[derive(Debug)]
+pub struct SizeStats {
+    pub groups: usize,
+    /// Groups of this size where everyone answered exactly the same questions.
+    pub full_agreement: usize,
+    /// People in groups of this size who answered each question, `a` first.
+    pub question_counts: [usize; 26],
+}
+
+impl SizeStats {
+    /// The share of people in groups of this size who answered each question.
+    pub fn frequencies(&self, size: usize) -> Vec<f64> {
+        let people = (self.groups * size) as f64;
+        self.question_counts
+            .iter()
+            .map(|&count| count as f64 / people)
+            .collect()
+    }
+}
+
+#[derive(Debug)]
+pub struct CustomsReport {
+    pub questions: Vec<QuestionStats>,
+    /// The questions the most people answered, more than one if there's a tie.
+    pub most_popular: Vec<char>,
+    pub least_popular: Vec<char>,
+    pub sizes: BTreeMap<usize, SizeStats>,
+    /// The starting lines of the groups where everyone answered exactly the same questions.
+    pub full_agreement: Vec<usize>,
+}
+
+pub fn customs_report(groups: &[Group]) -> CustomsReport {
+    let questions = question_stats(groups);
+    let most = questions.iter().map(|q| q.people).max().unwrap_or(0);
+    let least = questions.iter().map(|q| q.people).min().unwrap_or(0);
+    let with_count = |people| {
+        questions
+            .iter()
+            .filter(|q| q.people == people)
+            .map(|q| q.question)
+            .collect()
+    };
+    let (most_popular, least_popular) = (with_count(most), with_count(least));
+
+    let mut sizes = BTreeMap::new();
+    let mut full_agreement = Vec::new();
+    for group in groups {
+        let agreed = group.union() == group.intersection();
+        if agreed {
+            full_agreement.push(group.line);
+        }
+        let stats = sizes.entry(group.size()).or_insert(SizeStats {
+            groups: 0,
+            full_agreement: 0,
+            question_counts: [0; 26],
+        });
+        stats.groups += 1;
+        if agreed {
+            stats.full_agreement += 1;
+        }
+        for (total, count) in stats
+            .question_counts
+            .iter_mut()
+            .zip(group.question_counts())
+        {
+            *total += count;
+        }
+    }
+
+    CustomsReport {
+        questions,
+        most_popular,
+        least_popular,
+        sizes,
+        full_agreement,
+    }
+}
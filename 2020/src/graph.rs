@@ -0,0 +1,214 @@
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::ops::Range;
+
+pub type NodeId = usize;
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum EdgeDirection {
+    Forward,
+    Reverse,
+}
+
+/// A weighted directed graph whose nodes are named by strings. Names are interned once, so
+/// everything past construction works on plain `NodeId`s.
+#[derive(Clone, Debug)]
+pub struct Graph<W> {
+    names: Vec<String>,
+    ids: HashMap<String, NodeId>,
+    forward: Vec<Vec<(NodeId, W)>>,
+    reverse: Vec<Vec<(NodeId, W)>>,
+}
+
+impl<W: Copy> Graph<W> {
+    pub fn new() -> Graph<W> {
+        Graph {
+            names: Vec::new(),
+            ids: HashMap::new(),
+            forward: Vec::new(),
+            reverse: Vec::new(),
+        }
+    }
+
+    pub fn add_node(&mut self, name: &str) -> NodeId {
+        if let Some(&id) = self.ids.get(name) {
+            return id;
+        }
+        let id = self.names.len();
+        self.names.push(String::from(name));
+        self.ids.insert(String::from(name), id);
+        self.forward.push(Vec::new());
+        self.reverse.push(Vec::new());
+        id
+    }
+
+    pub fn add_edge(&mut self, from: &str, to: &str, weight: W) {
+        let (from, to) = (self.add_node(from), self.add_node(to));
+        self.forward[from].push((to, weight));
+        self.reverse[to].push((from, weight));
+    }
+
+    pub fn nodes(&self) -> Range<NodeId> {
+        0..self.names.len()
+    }
+
+    pub fn id(&self, name: &str) -> Option<NodeId> {
+        self.ids.get(name).copied()
+    }
+
+    pub fn name(&self, id: NodeId) -> &str {
+        &self.names[id]
+    }
+
+    pub fn successors(&self, id: NodeId) -> &[(NodeId, W)] {
+        &self.forward[id]
+    }
+
+    pub fn predecessors(&self, id: NodeId) -> &[(NodeId, W)] {
+        &self.reverse[id]
+    }
+
+    pub fn neighbors(&self, id: NodeId, direction: EdgeDirection) -> &[(NodeId, W)] {
+        match direction {
+            EdgeDirection::Forward => self.successors(id),
+            EdgeDirection::Reverse => self.predecessors(id),
+        }
+    }
+
+    /// Visits `start` and everything reachable from it, following each branch to the end first.
+    pub fn dfs(&self, start: NodeId, direction: EdgeDirection) -> Dfs<'_, W> {
+        Dfs {
+            graph: self,
+            direction,
+            stack: vec![start],
+            seen: HashSet::new(),
+        }
+    }
+
+    /// Every node reachable from `start` through at least one edge. `start` itself is only
+    /// included if it sits on a cycle.
+    pub fn reachable(&self, start: NodeId, direction: EdgeDirection) -> HashSet<NodeId> {
+        let mut found: HashSet<NodeId> = self.dfs(start, direction).skip(1).collect();
+        let back = match direction {
+            EdgeDirection::Forward => EdgeDirection::Reverse,
+            EdgeDirection::Reverse => EdgeDirection::Forward,
+        };
+        if self
+            .neighbors(start, back)
+            .iter()
+            .any(|&(n, _)| n == start || found.contains(&n))
+        {
+            found.insert(start);
+        }
+        found
+    }
+
+    /// Orders the nodes reachable from `roots` so that every edge points from an earlier node
+    /// to a later one. If the subgraph has a cycle there is no such order, and one of the
+    /// cycles is returned instead, listed in edge order.
+    pub fn topological_sort_from(&self, roots: &[NodeId]) -> Result<Vec<NodeId>, Vec<NodeId>> {
+        self.topological_sort_until(roots, |_| false)
+    }
+
+    /// Like `topological_sort_from`, but nodes for which `done` returns true are left out and
+    /// never walked past, so work already finished for them isn't repeated.
+    pub fn topological_sort_until(
+        &self,
+        roots: &[NodeId],
+        done: impl Fn(NodeId) -> bool,
+    ) -> Result<Vec<NodeId>, Vec<NodeId>> {
+        let mut nodes = HashSet::new();
+        let mut stack: Vec<NodeId> = roots.iter().copied().filter(|&n| !done(n)).collect();
+        while let Some(n) = stack.pop() {
+            if nodes.insert(n) {
+                stack.extend(
+                    self.successors(n)
+                        .iter()
+                        .map(|&(m, _)| m)
+                        .filter(|&m| !done(m)),
+                );
+            }
+        }
+
+        let mut in_degree: HashMap<NodeId, usize> = nodes.iter().map(|&n| (n, 0)).collect();
+        for &n in &nodes {
+            for (m, _) in self.successors(n) {
+                if let Some(degree) = in_degree.get_mut(m) {
+                    *degree += 1;
+                }
+            }
+        }
+
+        let mut ready: VecDeque<NodeId> = roots
+            .iter()
+            .copied()
+            .filter(|n| in_degree.get(n) == Some(&0))
+            .collect();
+        let mut order = Vec::new();
+        while let Some(n) = ready.pop_front() {
+            order.push(n);
+            for (m, _) in self.successors(n) {
+                if let Some(degree) = in_degree.get_mut(m) {
+                    *degree -= 1;
+                    if *degree == 0 {
+                        ready.push_back(*m);
+                    }
+                }
+            }
+        }
+
+        if order.len() == nodes.len() {
+            return Ok(order);
+        }
+
+        // Every node left over still has a predecessor that is also left over, so walking
+        // backwards through them has to come around to a node it has already seen.
+        let mut walk = vec![*in_degree.iter().find(|&(_, &d)| d > 0).unwrap().0];
+        loop {
+            let cur = *walk.last().unwrap();
+            let prev = self
+                .predecessors(cur)
+                .iter()
+                .map(|&(p, _)| p)
+                .find(|p| in_degree.get(p).is_some_and(|&d| d > 0))
+                .unwrap();
+            if let Some(pos) = walk.iter().position(|&n| n == prev) {
+                let mut cycle = walk.split_off(pos);
+                cycle.reverse();
+                return Err(cycle);
+            }
+            walk.push(prev);
+        }
+    }
+}
+
+impl<W: Copy> Default for Graph<W> {
+    fn default() -> Self {
+        Graph::new()
+    }
+}
+
+pub struct Dfs<'a, W> {
+    graph: &'a Graph<W>,
+    direction: EdgeDirection,
+    stack: Vec<NodeId>,
+    seen: HashSet<NodeId>,
+}
+
+impl<'a, W: Copy> Iterator for Dfs<'a, W> {
+    type Item = NodeId;
+
+    fn next(&mut self) -> Option<NodeId> {
+        while let Some(n) = self.stack.pop() {
+            if self.seen.insert(n) {
+                // pushed in reverse so neighbors come off the stack in insertion order
+                for &(m, _) in self.graph.neighbors(n, self.direction).iter().rev() {
+                    if !self.seen.contains(&m) {
+                        self.stack.push(m);
+                    }
+                }
+                return Some(n);
+            }
+        }
+        None
+    }
+}
@@ -0,0 +1,188 @@
+/// Finds `k` entries of `nums` at distinct indices that add up to `target`, returning their
+/// indices in ascending order.
+///
+/// The values are sorted once, after which every level but the last two fixes one entry and
+/// the last two are found with a pair of pointers closing in from either end, which makes it
+/// O(n^(k-1)) overall (and O(n log n) for `k` of 1 or 2).
+pub fn k_sum(nums: &[i64], k: usize, target: i64) -> Option<Vec<usize>> {
+    let mut sorted: Vec<(i64, usize)> = nums.iter().copied().zip(0..).collect();
+    sorted.sort_unstable();
+
+    let mut chosen = Vec::with_capacity(k);
+    if k_sum_sorted(&sorted, k, target, &mut chosen) {
+        chosen.sort_unstable();
+        Some(chosen)
+    } else {
+        None
+    }
+}
+
+fn k_sum_sorted(sorted: &[(i64, usize)], k: usize, target: i64, chosen: &mut Vec<usize>) -> bool {
+    match k {
+        0 => target == 0,
+        1 => match sorted.binary_search_by_key(&target, |&(v, _)| v) {
+            Ok(pos) => {
+                chosen.push(sorted[pos].1);
+                true
+            }
+            Err(_) => false,
+        },
+        2 => {
+            if sorted.len() < 2 {
+                return false;
+            }
+            let (mut lo, mut hi) = (0, sorted.len() - 1);
+            while lo < hi {
+                // widened so extreme values can't overflow the comparison
+                let sum = sorted[lo].0 as i128 + sorted[hi].0 as i128;
+                if sum == target as i128 {
+                    chosen.push(sorted[lo].1);
+                    chosen.push(sorted[hi].1);
+                    return true;
+                } else if sum < target as i128 {
+                    lo += 1;
+                } else {
+                    hi -= 1;
+                }
+            }
+            false
+        }
+        _ => {
+            for i in 0..sorted.len() {
+                let (value, idx) = sorted[i];
+                if let Some(rest) = target.checked_sub(value) {
+                    chosen.push(idx);
+                    if k_sum_sorted(&sorted[i + 1..], k - 1, rest, chosen) {
+                        return true;
+                    }
+                    chosen.pop();
+                }
+            }
+            false
+        }
+    }
+}
+
+/// Starts an iterator over every combination of `k` distinct indices of `nums` whose values
+/// add up to `target`, each given in ascending index order.
+pub fn k_sums(nums: &[i64], k: usize, target: i64) -> KSums {
+    let mut sorted: Vec<(i64, usize)> = nums.iter().copied().zip(0..).collect();
+    sorted.sort_unstable();
+
+    KSums {
+        sorted,
+        k,
+        target: target as i128,
+        distinct_values: false,
+        prefix: Vec::new(),
+        started: false,
+        remaining: 0,
+        cursor: None,
+        yield_empty: k == 0 && target == 0,
+    }
+}
+
+/// Every combination but the last index is walked like an odometer over the sorted values;
+/// the last index is then whatever completes the sum, found by binary search.
+pub struct KSums {
+    sorted: Vec<(i64, usize)>,
+    k: usize,
+    target: i128,
+    distinct_values: bool,
+    prefix: Vec<usize>,
+    started: bool,
+    remaining: i128,
+    cursor: Option<usize>,
+    yield_empty: bool,
+}
+
+impl KSums {
+    /// Only yields one combination for each distinct set of values, so `[1, 1, 2]` summing
+    /// to 3 gives one answer instead of two.
+    pub fn distinct_values(mut self) -> KSums {
+        self.distinct_values = true;
+        self
+    }
+
+    /// The first position after `pos` whose value can be picked at the same level, which with
+    /// `distinct_values` means skipping over everything equal to the value at `pos`.
+    fn next_position(&self, pos: usize) -> usize {
+        if self.distinct_values {
+            let value = self.sorted[pos].0;
+            pos + self.sorted[pos..].partition_point(|&(v, _)| v == value)
+        } else {
+            pos + 1
+        }
+    }
+
+    fn advance_prefix(&mut self) -> bool {
+        let (n, len) = (self.sorted.len(), self.k - 1);
+        if !self.started {
+            self.started = true;
+            if n < self.k {
+                return false;
+            }
+            self.prefix = (0..len).collect();
+            return true;
+        }
+
+        // leave room after each slot for the slots that follow it and the final index
+        for j in (0..len).rev() {
+            let next = self.next_position(self.prefix[j]);
+            if next + (self.k - j) <= n {
+                self.prefix.truncate(j);
+                self.prefix.extend(next..next + (len - j));
+                return true;
+            }
+        }
+        false
+    }
+
+    fn combination(&self, last: usize) -> Vec<usize> {
+        let mut indices: Vec<usize> = self
+            .prefix
+            .iter()
+            .chain(Some(&last))
+            .map(|&p| self.sorted[p].1)
+            .collect();
+        indices.sort_unstable();
+        indices
+    }
+}
+
+impl Iterator for KSums {
+    type Item = Vec<usize>;
+
+    fn next(&mut self) -> Option<Vec<usize>> {
+        if self.k == 0 {
+            let empty = self.yield_empty;
+            self.yield_empty = false;
+            return if empty { Some(Vec::new()) } else { None };
+        }
+
+        loop {
+            if let Some(p) = self.cursor {
+                if p < self.sorted.len() && self.sorted[p].0 as i128 == self.remaining {
+                    self.cursor = if self.distinct_values {
+                        None
+                    } else {
+                        Some(p + 1)
+                    };
+                    return Some(self.combination(p));
+                }
+                self.cursor = None;
+            }
+
+            if !self.advance_prefix() {
+                return None;
+            }
+            let used: i128 = self.prefix.iter().map(|&p| self.sorted[p].0 as i128).sum();
+            self.remaining = self.target - used;
+            let first = self.prefix.last().map_or(0, |&p| p + 1);
+            let remaining = self.remaining;
+            self.cursor = Some(
+                first + self.sorted[first..].partition_point(|&(v, _)| (v as i128) < remaining),
+            );
+        }
+    }
+}
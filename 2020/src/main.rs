@@ -1,15 +1,56 @@
-use std::collections::{HashMap, HashSet};
+mod adapters;
+mod bags;
+mod count;
+mod customs;
+mod graph;
+mod ksum;
+mod passport;
+mod passwords;
+mod records;
+mod seats;
+mod vm;
+mod xmas;
+
+use std::env;
 use std::error::Error;
-use std::fs::File;
-use std::io::{BufRead, BufReader};
+use std::fmt;
+use std::fs::{self, File};
+use std::io::{self, BufRead, BufReader};
 use std::process;
-
-use lazy_static::lazy_static;
-use regex::Regex;
-
-const NOT_IMPL: i64 = -1;
+use std::str::FromStr;
+
+use adapters::{
+    calc_joltage_diffs, chain_report, count_arrangements, list_arrangements, ChainReport,
+    JoltageRules, LinkKind,
+};
+use bags::{
+    containment_paths, count_inner_bags, count_reachable_nodes, deepest_chain, find_bag,
+    format_chain, largest_bag, parse_bag_rules, to_dot, to_json,
+};
+use count::{checked_mul, Count};
+use customs::{customs_report, question_stats, CustomsReport, Group};
+use ksum::{k_sum, k_sums};
+use passport::{
+    passports_from_csv, passports_to_csv, read_passports, Passport, PassportEntry, Ruleset,
+};
+use passwords::{
+    audit, parse_password_entries, PasswordPolicy, PolicyRegistry, SledPolicy, TobogganPolicy,
+};
+use records::read_records;
+use seats::{SeatCodec, SeatMap};
+use vm::{Instruction, Machine, Operation, Status};
+use xmas::{contiguous_ranges, find_contiguous_range, invalid_numbers, weakness};
 
 fn main() {
+    let args: Vec<String> = env::args().skip(1).collect();
+    if args.is_empty() {
+        run_all_days();
+    } else {
+        run(|| run_command(&args));
+    }
+}
+
+fn run_all_days() {
     println!("Hello, world!");
 
     let (d1p1, d1p2) = run(&day1);
@@ -49,178 +90,899 @@ fn main() {
     println!("Day 12: p1 {} p2 {}", d12p1, d12p2);
 }
 
-fn run<F, T>(func: F) -> T
-where
-    F: FnOnce() -> Result<T, Box<dyn Error>>,
-{
-    return func().unwrap_or_else(|err| {
-        eprintln!("Something bad happened: {}", err);
-        process::exit(1);
-    });
+fn run_command(args: &[String]) -> Result<(), Box<dyn Error>> {
+    match args[0].as_str() {
+        "vm" => vm_command(&args[1..]),
+        "bags" => bags_command(&args[1..]),
+        "expenses" => expenses_command(&args[1..]),
+        "xmas" => xmas_command(&args[1..]),
+        "adapters" => adapters_command(&args[1..]),
+        "seats" => seats_command(&args[1..]),
+        "passports" => passports_command(&args[1..]),
+        "customs" => customs_command(&args[1..]),
+        "passwords" => passwords_command(&args[1..]),
+        other => Err(format!("unknown command: {}", other).into()),
+    }
 }
 
-fn read_lines(day: i32) -> Result<Vec<String>, Box<dyn Error>> {
-    let f = File::open(format!("./input/day{}.txt", day))?;
-    let lines = BufReader::new(f).lines();
+fn vm_command(args: &[String]) -> Result<(), Box<dyn Error>> {
+    match args {
+        [cmd, steps, path] if cmd == "snapshot" => {
+            let mut machine = Machine::new(&read_program(8)?);
+            let status = machine.run_for(steps.parse()?);
+            machine.save(path)?;
+            println!(
+                "Saved {:?} machine to {}: ip {} acc {} after {} steps",
+                status,
+                path,
+                machine.ip(),
+                machine.acc(),
+                machine.steps()
+            );
+            Ok(())
+        }
+        [cmd, path] if cmd == "replay" => {
+            let mut machine = Machine::load(path)?;
+            let resumed_at = machine.steps();
+            let status = machine.run();
+            println!(
+                "Resumed at step {}: {:?} at ip {} acc {} after {} steps",
+                resumed_at,
+                status,
+                machine.ip(),
+                machine.acc(),
+                machine.steps()
+            );
+            Ok(())
+        }
+        _ => Err("usage: vm snapshot <steps> <file> | vm replay <file>".into()),
+    }
+}
 
-    let mut buffer = Vec::new();
-    for line in lines {
-        if let Ok(line) = line {
-            buffer.push(line);
+fn bags_command(args: &[String]) -> Result<(), Box<dyn Error>> {
+    const USAGE: &str = concat!(
+        "usage: bags containers <color> [--limit <n>] | bags count <color> | ",
+        "bags deepest [<color>] | bags largest | bags export dot [<root color>] | ",
+        "bags export json"
+    );
+
+    let rules = parse_bag_rules(&read_lines(7)?)?;
+
+    match args {
+        [cmd, color @ ..] if cmd == "containers" && !color.is_empty() => {
+            let (color, limit) = match color {
+                [color @ .., option, n] if option == "--limit" => (color, n.parse()?),
+                _ => (color, 1000),
+            };
+            if color.is_empty() {
+                return Err(USAGE.into());
+            }
+            let color = color.join(" ");
+            let target = find_bag(&rules, &color)?;
+            let paths = containment_paths(&rules, target, limit);
+            println!(
+                "{} bag colors can eventually contain {}:",
+                count_reachable_nodes(&color, &rules),
+                color
+            );
+            for path in &paths {
+                println!("  {}", format_chain(&rules, path));
+            }
+            if paths.len() >= limit {
+                println!("Stopped listing paths after {}", limit);
+            }
+            Ok(())
+        }
+        [cmd, color @ ..] if cmd == "count" && !color.is_empty() => {
+            let color = color.join(" ");
+            find_bag(&rules, &color)?;
+            println!("{} holds {} bags", color, count_inner_bags(&color, &rules)?);
+            Ok(())
+        }
+        [cmd, color @ ..] if cmd == "deepest" => {
+            let start = if color.is_empty() {
+                None
+            } else {
+                Some(find_bag(&rules, &color.join(" "))?)
+            };
+            let chain = deepest_chain(&rules, start)?;
+            println!(
+                "{} levels: {}",
+                chain.len().saturating_sub(1),
+                format_chain(&rules, &chain)
+            );
+            Ok(())
+        }
+        [cmd] if cmd == "largest" => {
+            match largest_bag(&rules)? {
+                Some((bag, total)) => println!("{} holds {} bags", rules.name(bag), total),
+                None => println!("no bag rules"),
+            }
+            Ok(())
+        }
+        [cmd, format, root @ ..] if cmd == "export" => {
+            match format.as_str() {
+                "dot" => {
+                    let root = if root.is_empty() {
+                        None
+                    } else {
+                        Some(find_bag(&rules, &root.join(" "))?)
+                    };
+                    print!("{}", to_dot(&rules, root));
+                }
+                "json" if root.is_empty() => println!("{}", to_json(&rules)?),
+                "json" => return Err(USAGE.into()),
+                other => return Err(format!("unknown export format: {}", other).into()),
+            }
+            Ok(())
         }
+        _ => Err(USAGE.into()),
     }
-    Ok(buffer)
 }
 
-fn read_grid(day: i32) -> Result<Vec<Vec<char>>, Box<dyn Error>> {
-    let mut grid = vec![];
-    let lines = read_lines(day)?;
+fn expenses_command(args: &[String]) -> Result<(), Box<dyn Error>> {
+    let distinct_values = args.iter().any(|a| a == "--distinct-values");
+    let args: Vec<&String> = args.iter().filter(|&a| a != "--distinct-values").collect();
+    let (k, target) = match args.as_slice() {
+        [k] => (k.parse()?, 2020),
+        [k, target] => (k.parse()?, target.parse()?),
+        _ => return Err("usage: expenses <k> [<target>] [--distinct-values]".into()),
+    };
 
-    for line in lines {
-        grid.push(line.chars().collect());
+    let nums: Vec<i64> = read_lines(1)?
+        .iter()
+        .map(|s| s.parse())
+        .collect::<Result<_, _>>()?;
+    let mut sums = k_sums(&nums, k, target);
+    if distinct_values {
+        sums = sums.distinct_values();
     }
 
-    Ok(grid)
+    let mut count = 0;
+    for indices in sums {
+        let values: Vec<String> = indices.iter().map(|&i| nums[i].to_string()).collect();
+        let product = expense_product(&nums, &indices)?;
+        println!(
+            "lines {:?}: {} = {} (product {})",
+            indices.iter().map(|i| i + 1).collect::<Vec<_>>(),
+            values.join(" + "),
+            target,
+            product
+        );
+        count += 1;
+    }
+    match count {
+        0 => println!("No {} entries sum to {}", k, target),
+        1 => println!("The answer is unique"),
+        n => println!("{} different answers", n),
+    }
+    Ok(())
 }
 
-fn print_grid(grid: &[Vec<char>]) -> () {
-    for line in grid {
-        let mut row_str = String::new();
-        for c in line {
-            row_str.push(*c);
+fn xmas_command(args: &[String]) -> Result<(), Box<dyn Error>> {
+    const USAGE: &str = "usage: xmas invalid|weakness [--preamble <n>] [--all] [-]";
+
+    let (mut preamble, mut all, mut from_stdin) = (25, false, false);
+    let mut options = args.iter().skip(1);
+    while let Some(option) = options.next() {
+        match option.as_str() {
+            "--preamble" => preamble = options.next().ok_or(USAGE)?.parse()?,
+            "--all" => all = true,
+            "-" => from_stdin = true,
+            other => return Err(format!("unknown option {}; {}", other, USAGE).into()),
         }
-        println!("{}", row_str);
+    }
+    let numbers = xmas_numbers(from_stdin)?;
+
+    match args.first().map(String::as_str) {
+        Some("invalid") => {
+            // stop at the first unreadable line rather than skipping it, so positions stay
+            // accurate
+            let mut bad_line = None;
+            let numbers = numbers.map_while(|n| n.map_err(|e| bad_line = Some(e)).ok());
+
+            let mut count = 0;
+            for (position, n) in invalid_numbers(numbers, preamble) {
+                println!("line {}: {}", position + 1, n);
+                count += 1;
+            }
+            if let Some(e) = bad_line {
+                return Err(e.into());
+            }
+            println!("{} invalid numbers", count);
+            Ok(())
+        }
+        Some("weakness") => {
+            let data: Vec<i64> = numbers.collect::<Result<_, _>>()?;
+            let invalid = match invalid_numbers(data.iter().copied(), preamble).next() {
+                Some((_, n)) => n,
+                None => return Err("every number in the stream is valid".into()),
+            };
+            let ranges = if all {
+                contiguous_ranges(&data, invalid)
+            } else {
+                find_contiguous_range(&data, invalid).into_iter().collect()
+            };
+
+            if ranges.is_empty() {
+                println!("No contiguous range adds up to {}", invalid);
+            }
+            for range in ranges {
+                println!(
+                    "lines {}-{}: weakness {}",
+                    range.start + 1,
+                    range.end,
+                    weakness(&data[range.clone()]).unwrap()
+                );
+            }
+            Ok(())
+        }
+        _ => Err(USAGE.into()),
     }
 }
 
-fn read_entries(day: i32, separator: &str) -> Result<Vec<HashMap<String, String>>, Box<dyn Error>> {
-    let f = File::open(format!("./input/day{}.txt", day))?;
-    let lines = BufReader::new(f).lines();
+type XmasNumbers = Box<dyn Iterator<Item = Result<i64, String>>>;
+
+/// The day 9 numbers, or stdin's, labelled with their line number when they don't parse.
+fn xmas_numbers(from_stdin: bool) -> Result<XmasNumbers, Box<dyn Error>> {
+    let lines: Box<dyn Iterator<Item = io::Result<String>>> = if from_stdin {
+        Box::new(io::stdin().lock().lines())
+    } else {
+        Box::new(read_lines(9)?.into_iter().map(Ok))
+    };
 
-    let mut entries = vec![];
+    Ok(Box::new(lines.enumerate().map(|(i, line)| {
+        let parsed = match line {
+            Ok(l) => l
+                .trim()
+                .parse::<i64>()
+                .map_err(|e| format!("{:?}: {}", l, e)),
+            Err(e) => Err(e.to_string()),
+        };
+        parsed.map_err(|e| format!("line {}: {}", i + 1, e))
+    })))
+}
+
+fn adapters_command(args: &[String]) -> Result<(), Box<dyn Error>> {
+    const USAGE: &str = "usage: adapters diffs|arrangements|report [--list [<limit>]] [--json] \
+                         [--steps <n>,<n>,...] [--device-offset <n>]";
+
+    let (mut steps, mut device_offset) = (vec![1, 2, 3], 3);
+    let (mut limit, mut json) = (None, false);
+    let mut options = args.iter().skip(1).peekable();
+    while let Some(option) = options.next() {
+        match option.as_str() {
+            "--list" => {
+                limit = match options.peek().and_then(|n| n.parse().ok()) {
+                    Some(n) => {
+                        options.next();
+                        Some(n)
+                    }
+                    None => Some(1000),
+                }
+            }
+            "--steps" => {
+                steps = options
+                    .next()
+                    .ok_or(USAGE)?
+                    .split(',')
+                    .map(|s| s.trim().parse())
+                    .collect::<Result<_, _>>()?
+            }
+            "--device-offset" => device_offset = options.next().ok_or(USAGE)?.parse()?,
+            "--json" => json = true,
+            other => return Err(format!("unknown option {}; {}", other, USAGE).into()),
+        }
+    }
 
-    let mut cur_entry = HashMap::new();
-    for line in lines {
-        if let Ok(line) = line {
-            if line == separator {
-                entries.push(cur_entry);
-                cur_entry = HashMap::new();
-            } else {
-                for field in line.split_ascii_whitespace() {
-                    let kv: Vec<_> = field.split(':').collect();
-                    cur_entry.insert(String::from(kv[0].trim()), String::from(kv[1].trim()));
+    let rules = JoltageRules::new(&steps, device_offset)?;
+    let data: Vec<i32> = read_lines(10)?
+        .iter()
+        .map(|d| d.parse())
+        .collect::<Result<_, _>>()?;
+    let chain = rules.full_chain(&data);
+
+    match args.first().map(String::as_str) {
+        Some("diffs") => {
+            for (step, count) in calc_joltage_diffs(&chain, &rules)? {
+                println!("{} jolt steps: {}", step, count);
+            }
+            Ok(())
+        }
+        Some("arrangements") => {
+            let count = count_arrangements(&chain, &rules)?;
+            println!("{} arrangements", count);
+
+            if let Some(limit) = limit {
+                let arrangements = list_arrangements(&chain, &rules, limit);
+                for arrangement in &arrangements {
+                    let joltages: Vec<String> = arrangement.iter().map(|j| j.to_string()).collect();
+                    println!("(0) {} ({})", joltages.join(" "), chain.last().unwrap());
                 }
+                if arrangements.len() < limit {
+                    let listed = Count::from(arrangements.len() as u64);
+                    if listed != count {
+                        return Err(format!(
+                            "listed {} arrangements but counted {}",
+                            listed, count
+                        )
+                        .into());
+                    }
+                    println!("Listed all {} arrangements", listed);
+                } else {
+                    println!("Stopped listing after {}", limit);
+                }
+            }
+            Ok(())
+        }
+        Some("report") => {
+            let report = chain_report(&chain, &rules)?;
+            if json {
+                println!("{}", serde_json::to_string_pretty(&report)?);
+            } else {
+                print_chain_report(&report);
             }
+            Ok(())
         }
+        _ => Err(USAGE.into()),
     }
+}
 
-    if cur_entry.len() > 0 {
-        entries.push(cur_entry);
+fn print_chain_report(report: &ChainReport) {
+    println!("joltage  step");
+    for link in &report.chain {
+        let step = match link.step {
+            Some(step) => step.to_string(),
+            None => String::from("-"),
+        };
+        let note = match link.kind {
+            LinkKind::Outlet => "outlet",
+            LinkKind::Device => "device",
+            LinkKind::Adapter if link.mandatory => "mandatory",
+            LinkKind::Adapter => "",
+        };
+        let row = format!("{:>7}  {:>4}  {}", link.joltage, step, note);
+        println!("{}", row.trim_end());
     }
+    println!();
 
-    Ok(entries)
+    for (step, count) in &report.histogram {
+        println!("{} jolt steps: {}", step, count);
+    }
+    let adapters = report.chain.len() - 2;
+    println!(
+        "{} of {} adapters are mandatory",
+        report.mandatory.len(),
+        adapters
+    );
 }
 
-fn day1() -> Result<(i64, i64), Box<dyn Error>> {
-    let nums: Vec<i64> = read_lines(1)?.iter().map(|s| s.parse().unwrap()).collect();
+fn seats_command(args: &[String]) -> Result<(), Box<dyn Error>> {
+    const USAGE: &str =
+        "usage: seats decode <pass>|encode <seat id>|map|gaps [--rows <n>] [--cols <n>]";
+
+    let skip = match args.first().map(String::as_str) {
+        Some("decode") | Some("encode") => 2,
+        _ => 1,
+    };
+    let (mut num_rows, mut num_cols) = (128, 8);
+    let mut options = args.iter().skip(skip);
+    while let Some(option) = options.next() {
+        match option.as_str() {
+            "--rows" => num_rows = options.next().ok_or(USAGE)?.parse()?,
+            "--cols" => num_cols = options.next().ok_or(USAGE)?.parse()?,
+            other => return Err(format!("unknown option {}; {}", other, USAGE).into()),
+        }
+    }
+    let codec = SeatCodec::new(num_rows, num_cols)?;
+
+    match args {
+        [cmd, pass, ..] if cmd == "decode" => {
+            let seat = codec.decode(pass)?;
+            println!(
+                "{}: row {} column {} seat ID {}",
+                pass,
+                seat.row,
+                seat.col,
+                codec.seat_id(seat)
+            );
+            Ok(())
+        }
+        [cmd, seat_id, ..] if cmd == "encode" => {
+            let seat_id = seat_id.parse()?;
+            let seat = codec.seat(seat_id);
+            println!(
+                "seat ID {}: row {} column {} pass {}",
+                seat_id,
+                seat.row,
+                seat.col,
+                codec.encode(seat_id)?
+            );
+            Ok(())
+        }
+        [cmd, ..] if cmd == "map" => {
+            print!("{}", SeatMap::new(codec, &read_lines(5)?)?.render());
+            Ok(())
+        }
+        [cmd, ..] if cmd == "gaps" => {
+            let seat_map = SeatMap::new(codec, &read_lines(5)?)?;
 
-    let (x, y) = two_sum(&nums, 2020);
-    let (n, p, q) = three_sum(&nums, 2020);
-    Ok((x * y, n * p * q))
+            println!("Free seats:");
+            for (first, last) in seat_map.free_ranges() {
+                if first == last {
+                    println!("  {}", first);
+                } else {
+                    println!("  {}-{} ({} seats)", first, last, last - first + 1);
+                }
+            }
+
+            let duplicates = seat_map.duplicates();
+            if duplicates.is_empty() {
+                println!("No duplicate boarding passes");
+            }
+            for duplicate in duplicates {
+                let lines: Vec<String> = duplicate.lines.iter().map(|l| l.to_string()).collect();
+                println!(
+                    "Seat {} is on more than one pass, on lines {}",
+                    duplicate.seat_id,
+                    lines.join(", ")
+                );
+            }
+
+            let candidates: Vec<String> = seat_map
+                .candidate_seats()
+                .iter()
+                .map(|id| id.to_string())
+                .collect();
+            println!(
+                "Free seats between two taken ones: {}",
+                if candidates.is_empty() {
+                    String::from("none")
+                } else {
+                    candidates.join(", ")
+                }
+            );
+            Ok(())
+        }
+        _ => Err(USAGE.into()),
+    }
 }
 
-fn two_sum(nums: &[i64], target: i64) -> (i64, i64) {
-    let mut complements = HashSet::new();
+fn passports_command(args: &[String]) -> Result<(), Box<dyn Error>> {
+    const USAGE: &str = "usage: passports validate|report [--rules <file>] [--json] \
+                         [--input <file.json|file.csv>] | \
+                         passports export json|csv [--input <file.json|file.csv>]";
 
-    for n in nums {
-        let comp = target - n;
-        if complements.contains(n) && *n != comp {
-            return (*n, comp);
-        } else {
-            complements.insert(comp);
+    let (mut rules, mut input, mut json) = (Ruleset::default(), None, false);
+    let export = args.first().map(String::as_str) == Some("export");
+    let mut options = args.iter().skip(if export { 2 } else { 1 });
+    while let Some(option) = options.next() {
+        match option.as_str() {
+            // export writes out every passport as it is, so rules would have nothing to do
+            "--rules" if export => {
+                return Err(format!("export doesn't check rules; {}", USAGE).into())
+            }
+            "--rules" => rules = Ruleset::load(options.next().ok_or(USAGE)?)?,
+            "--input" => input = Some(options.next().ok_or(USAGE)?),
+            "--json" => json = true,
+            other => return Err(format!("unknown option {}; {}", other, USAGE).into()),
+        }
+    }
+
+    let read_entries = || -> Result<Vec<PassportEntry>, Box<dyn Error>> {
+        match input {
+            Some(path) => Ok(numbered_entries(&import_passports(path)?)),
+            None => Ok(read_passports(&read_lines(4)?, &rules)?),
+        }
+    };
+    match args.first().map(String::as_str) {
+        Some("validate") => {
+            let (complete, valid) = count_valid_passports(&read_entries()?, &rules);
+            println!("{} passports have every required field", complete);
+            println!("{} passports are valid", valid);
+        }
+        Some("report") => {
+            let reports: Vec<_> = read_entries()?.iter().map(|p| rules.report(p)).collect();
+            if json {
+                println!("{}", serde_json::to_string_pretty(&reports)?);
+            } else {
+                for report in &reports {
+                    println!("{}", report);
+                }
+                let valid = reports.iter().filter(|r| r.valid).count();
+                println!("{} of {} passports are valid", valid, reports.len());
+            }
+        }
+        Some("export") => {
+            let passports: Vec<Passport> = match input {
+                Some(path) => import_passports(path)?,
+                None => read_records(&read_lines(4)?)
+                    .iter()
+                    .map(|record| record.parse())
+                    .collect::<Result<_, _>>()?,
+            };
+            match args.get(1).map(String::as_str) {
+                Some("json") => println!("{}", serde_json::to_string_pretty(&passports)?),
+                Some("csv") => print!("{}", passports_to_csv(&passports)?),
+                _ => return Err(USAGE.into()),
+            }
         }
+        _ => return Err(USAGE.into()),
     }
-    (-1, -1)
+    Ok(())
+}
+
+/// Reads passports exported as JSON or CSV.
+fn import_passports(path: &str) -> Result<Vec<Passport>, Box<dyn Error>> {
+    let data = fs::read_to_string(path)?;
+    let passports: Vec<Passport> = if path.ends_with(".json") {
+        serde_json::from_str(&data).map_err(|e| format!("{}: {}", path, e))?
+    } else if path.ends_with(".csv") {
+        passports_from_csv(&data).map_err(|e| format!("{}: {}", path, e))?
+    } else {
+        return Err(format!("{}: expected a .json or .csv file", path).into());
+    };
+    Ok(passports)
 }
 
-fn three_sum(nums: &[i64], target: i64) -> (i64, i64, i64) {
-    for n in nums {
-        for p in nums {
-            for q in nums {
-                if n + p + q == target {
-                    return (*n, *p, *q);
+/// Imported passports have no lines to point at, so each is numbered by its position in
+/// the file instead.
+fn numbered_entries(passports: &[Passport]) -> Vec<PassportEntry> {
+    passports
+        .iter()
+        .enumerate()
+        .map(|(i, p)| PassportEntry {
+            line: i + 1,
+            fields: p.to_fields(),
+        })
+        .collect()
+}
+
+fn customs_command(args: &[String]) -> Result<(), Box<dyn Error>> {
+    const USAGE: &str = "usage: customs groups [--at-least <k>] | customs questions | \
+                         customs report [--csv questions|sizes|agreement]";
+
+    let (mut at_least, mut csv_table) = (None, None);
+    let mut options = args.iter().skip(1);
+    while let Some(option) = options.next() {
+        match option.as_str() {
+            "--at-least" => at_least = Some(options.next().ok_or(USAGE)?.parse::<usize>()?),
+            "--csv" => csv_table = Some(options.next().ok_or(USAGE)?.as_str()),
+            other => return Err(format!("unknown option {}; {}", other, USAGE).into()),
+        }
+    }
+
+    let groups = read_groups()?;
+    match args.first().map(String::as_str) {
+        Some("groups") => {
+            for group in &groups {
+                let mut row = format!(
+                    "line {:>4}: {} people, anyone {:<26} everyone {:<26} odd {:<26}",
+                    group.line,
+                    group.size(),
+                    group.union().to_string(),
+                    group.intersection().to_string(),
+                    group.symmetric_difference().to_string()
+                );
+                if let Some(k) = at_least {
+                    row += &format!(" at least {} {}", k, group.at_least(k));
                 }
+                println!("{}", row.trim_end());
+            }
+            if let Some(k) = at_least {
+                let total: usize = groups.iter().map(|g| g.at_least(k).len()).sum();
+                println!(
+                    "{} questions with at least {} yes answers in their group",
+                    total, k
+                );
             }
         }
+        Some("questions") => {
+            println!("question  people  groups (anyone)  groups (everyone)");
+            for stats in question_stats(&groups) {
+                println!(
+                    "{:>8}  {:>6}  {:>15}  {:>17}",
+                    stats.question, stats.people, stats.groups_any, stats.groups_all
+                );
+            }
+        }
+        Some("report") => {
+            let report = customs_report(&groups);
+            match csv_table {
+                Some(table) => print!("{}", customs_report_csv(&report, table)?),
+                None => print_customs_report(&report, groups.len()),
+            }
+        }
+        _ => return Err(USAGE.into()),
     }
-    (-1, -1, -1)
+    Ok(())
+}
+
+fn print_customs_report(report: &CustomsReport, num_groups: usize) {
+    let people = |chars: &[char]| {
+        let q: Vec<String> = chars.iter().map(|c| c.to_string()).collect();
+        let count = report.questions.iter().find(|s| s.question == chars[0]);
+        format!(
+            "{} ({} people)",
+            q.join(", "),
+            count.map_or(0, |s| s.people)
+        )
+    };
+    println!("most popular: {}", people(&report.most_popular));
+    println!("least popular: {}", people(&report.least_popular));
+    println!();
+
+    println!("size  groups  full agreement");
+    for (size, stats) in &report.sizes {
+        println!(
+            "{:>4}  {:>6}  {:>14}",
+            size, stats.groups, stats.full_agreement
+        );
+    }
+    println!(
+        "{} of {} groups answered in full agreement, starting on lines:",
+        report.full_agreement.len(),
+        num_groups
+    );
+    let lines: Vec<String> = report
+        .full_agreement
+        .iter()
+        .map(|l| l.to_string())
+        .collect();
+    for row in lines.chunks(15) {
+        println!("  {}", row.join(", "));
+    }
+    println!();
+
+    // darker means more of the people in groups of that size answered the question
+    const SHADES: [char; 5] = [' ', '.', ':', '+', '#'];
+    println!("size  abcdefghijklmnopqrstuvwxyz");
+    for (&size, stats) in &report.sizes {
+        let row: String = stats
+            .frequencies(size)
+            .iter()
+            .map(|&f| SHADES[((f * SHADES.len() as f64) as usize).min(SHADES.len() - 1)])
+            .collect();
+        println!("{}", format!("{:>4}  {}", size, row).trim_end());
+    }
+    println!("      ' ' under 20%, '.' under 40%, ':' under 60%, '+' under 80%, '#' the rest");
+}
+
+fn customs_report_csv(report: &CustomsReport, table: &str) -> Result<String, Box<dyn Error>> {
+    let mut writer = csv::Writer::from_writer(Vec::new());
+    match table {
+        "questions" => {
+            let mut header = vec![
+                String::from("question"),
+                String::from("people"),
+                String::from("groups_any"),
+                String::from("groups_all"),
+            ];
+            header.extend(
+                report
+                    .sizes
+                    .keys()
+                    .map(|size| format!("share_size_{}", size)),
+            );
+            writer.write_record(&header)?;
+            for (i, stats) in report.questions.iter().enumerate() {
+                let mut row = vec![
+                    stats.question.to_string(),
+                    stats.people.to_string(),
+                    stats.groups_any.to_string(),
+                    stats.groups_all.to_string(),
+                ];
+                for (&size, size_stats) in &report.sizes {
+                    row.push(format!("{:.3}", size_stats.frequencies(size)[i]));
+                }
+                writer.write_record(&row)?;
+            }
+        }
+        "sizes" => {
+            writer.write_record(["size", "groups", "full_agreement"])?;
+            for (size, stats) in &report.sizes {
+                writer.write_record([
+                    size.to_string(),
+                    stats.groups.to_string(),
+                    stats.full_agreement.to_string(),
+                ])?;
+            }
+        }
+        "agreement" => {
+            writer.write_record(["line"])?;
+            for line in &report.full_agreement {
+                writer.write_record([line.to_string()])?;
+            }
+        }
+        other => {
+            return Err(format!(
+                "unknown table {}; expected questions, sizes or agreement",
+                other
+            )
+            .into())
+        }
+    }
+    Ok(String::from_utf8(writer.into_inner()?)?)
 }
 
-fn day2() -> Result<(i32, i32), Box<dyn Error>> {
-    let mut sled = 0;
-    let mut toboggan = 0;
+fn passwords_command(args: &[String]) -> Result<(), Box<dyn Error>> {
+    const USAGE: &str = "usage: passwords check|audit [--policy <name>[:<argument>]]... [--json] \
+                         | passwords policies";
 
-    for line in read_lines(2)? {
-        let v: Vec<_> = line.split(':').collect();
-        let (policy, password) = (v[0].trim(), v[1].trim());
+    let registry = PolicyRegistry::default();
+    let (mut policy_specs, mut json) = (Vec::new(), false);
+    let mut options = args.iter().skip(1);
+    while let Some(option) = options.next() {
+        match option.as_str() {
+            "--policy" => policy_specs.push(options.next().ok_or(USAGE)?.as_str()),
+            "--json" => json = true,
+            other => return Err(format!("unknown option {}; {}", other, USAGE).into()),
+        }
+    }
+    if policy_specs.is_empty() {
+        policy_specs = vec!["sled", "toboggan"];
+    }
 
-        if check_password_against_sled_policy(policy, password) {
-            sled += 1;
+    let policies = || {
+        policy_specs
+            .iter()
+            .map(|spec| registry.create(spec))
+            .collect::<Result<Vec<_>, _>>()
+    };
+    match args.first().map(String::as_str) {
+        Some("check") => {
+            let policies = policies()?;
+            let entries = parse_password_entries(&read_lines(2)?)?;
+            for policy in &policies {
+                let valid = entries.iter().filter(|e| policy.check(e).is_ok()).count();
+                println!(
+                    "{}: {} of {} passwords are valid",
+                    policy.name(),
+                    valid,
+                    entries.len()
+                );
+            }
         }
-        if check_password_against_toboggan_policy(policy, password) {
-            toboggan += 1;
+        Some("audit") => {
+            let report = audit(&parse_password_entries(&read_lines(2)?)?, &policies()?);
+            if json {
+                println!("{}", serde_json::to_string_pretty(&report)?);
+            } else {
+                for policy in &report.policies {
+                    println!("{} policy:", policy.policy);
+                    for failure in &policy.failures {
+                        println!("  {}", failure);
+                    }
+                }
+                println!();
+                for policy in &report.policies {
+                    println!(
+                        "{}: {} passed, {} failed ({:.1}% passed)",
+                        policy.policy,
+                        policy.passed,
+                        policy.failed,
+                        100.0 * policy.passed as f64 / report.entries.max(1) as f64
+                    );
+                }
+                println!(
+                    "{} of {} passwords pass every policy, {} fail every policy",
+                    report.passed_all, report.entries, report.failed_all
+                );
+            }
+        }
+        Some("policies") => {
+            for (_, usage) in registry.policies() {
+                println!("{}", usage);
+            }
         }
+        _ => return Err(USAGE.into()),
     }
+    Ok(())
+}
 
-    Ok((sled, toboggan))
+fn run<F, T>(func: F) -> T
+where
+    F: FnOnce() -> Result<T, Box<dyn Error>>,
+{
+    return func().unwrap_or_else(|err| {
+        eprintln!("Something bad happened: {}", err);
+        process::exit(1);
+    });
 }
 
-fn check_password_against_sled_policy(policy: &str, password: &str) -> bool {
-    let w: Vec<_> = policy.split_ascii_whitespace().collect();
-    let (range_str, character_str) = (w[0].trim(), w[1].trim());
-    let range: Vec<_> = range_str.split('-').collect();
-    let (min, max) = (
-        range[0].parse::<usize>().unwrap(),
-        range[1].parse::<usize>().unwrap(),
-    );
+fn read_lines(day: i32) -> Result<Vec<String>, Box<dyn Error>> {
+    let f = File::open(format!("./input/day{}.txt", day))?;
+    let lines = BufReader::new(f).lines();
 
-    let count = count_char_in_str(character_str.chars().next().unwrap(), password);
-    count >= min && count <= max
+    let mut buffer = Vec::new();
+    for line in lines {
+        if let Ok(line) = line {
+            buffer.push(line);
+        }
+    }
+    Ok(buffer)
+}
+
+/// Reads one number per line, pointing at the line that doesn't parse.
+fn read_numbers<T>(day: i32) -> Result<Vec<T>, Box<dyn Error>>
+where
+    T: FromStr,
+    T::Err: fmt::Display,
+{
+    read_lines(day)?
+        .iter()
+        .enumerate()
+        .map(|(i, line)| {
+            line.trim()
+                .parse()
+                .map_err(|e| format!("day {} line {}: {:?}: {}", day, i + 1, line, e).into())
+        })
+        .collect()
+}
+
+fn read_grid(day: i32) -> Result<Vec<Vec<char>>, Box<dyn Error>> {
+    let mut grid = vec![];
+    let lines = read_lines(day)?;
+
+    for line in lines {
+        grid.push(line.chars().collect());
+    }
+
+    Ok(grid)
 }
 
-fn count_char_in_str(c: char, s: &str) -> usize {
-    s.chars().filter(|ch| *ch == c).count()
+fn print_grid(grid: &[Vec<char>]) -> () {
+    for line in grid {
+        let mut row_str = String::new();
+        for c in line {
+            row_str.push(*c);
+        }
+        println!("{}", row_str);
+    }
 }
 
-fn check_password_against_toboggan_policy(policy: &str, password: &str) -> bool {
-    let w: Vec<_> = policy.split_ascii_whitespace().collect();
-    let (pos_str, character_str) = (w[0].trim(), w[1].trim());
-    let posv: Vec<_> = pos_str.split('-').collect();
-    let (pos1, pos2) = (posv[0].parse(), posv[1].parse());
+fn day1() -> Result<(i64, i64), Box<dyn Error>> {
+    let nums: Vec<i64> = read_numbers(1)?;
 
-    xor_char_at_pos(
-        character_str.chars().next().unwrap(),
-        pos1.unwrap(),
-        pos2.unwrap(),
-        password,
-    )
+    let product = |indices: Option<Vec<usize>>| match indices {
+        Some(indices) => expense_product(&nums, &indices),
+        None => Ok(-1),
+    };
+    Ok((
+        product(k_sum(&nums, 2, 2020))?,
+        product(k_sum(&nums, 3, 2020))?,
+    ))
 }
 
-fn xor_char_at_pos(c: char, pos1: usize, pos2: usize, s: &str) -> bool {
-    let (p1, p2) = (pos1 - 1, pos2 - 1);
-    let cv: Vec<_> = s.chars().collect();
-    (cv[p1] == c && cv[p2] != c) || (cv[p1] != c && cv[p2] == c)
+fn expense_product(nums: &[i64], indices: &[usize]) -> Result<i64, Box<dyn Error>> {
+    indices
+        .iter()
+        .try_fold(1i64, |p, &i| p.checked_mul(nums[i]))
+        .ok_or_else(|| "expense product overflowed i64".into())
 }
 
-fn day3() -> Result<(u64, u64), Box<dyn Error>> {
+fn day2() -> Result<(i32, i32), Box<dyn Error>> {
+    let entries = parse_password_entries(&read_lines(2)?)?;
+
+    let count_valid = |policy: &dyn PasswordPolicy| {
+        entries.iter().filter(|e| policy.check(e).is_ok()).count() as i32
+    };
+    Ok((count_valid(&SledPolicy), count_valid(&TobogganPolicy)))
+}
+
+fn day3() -> Result<(u64, Count), Box<dyn Error>> {
     let grid = read_grid(3)?;
 
     let part1 = check_slope(&grid, 3, 1);
 
-    let mut total_trees_product = 1u64;
+    let mut total_trees_product = Count::from(1u64);
     let slopes = [(1, 1), (3, 1), (5, 1), (7, 1), (1, 2)];
     for &(x, y) in &slopes {
-        total_trees_product *= check_slope(&grid, x, y);
+        total_trees_product = checked_mul(
+            &total_trees_product,
+            &Count::from(check_slope(&grid, x, y)),
+            "the product of trees on each slope",
+        )?;
     }
 
     Ok((part1, total_trees_product))
@@ -254,195 +1016,45 @@ fn check_slope(grid: &Vec<Vec<char>>, mx: usize, my: usize) -> u64 {
 }
 
 fn day4() -> Result<(i32, i32), Box<dyn Error>> {
-    const REQUIRED_FIELDS: [&str; 7] = ["byr", "iyr", "eyr", "hgt", "hcl", "ecl", "pid"];
+    let rules = Ruleset::default();
+    let passport_entries = read_passports(&read_lines(4)?, &rules)?;
+
+    Ok(count_valid_passports(&passport_entries, &rules))
+}
 
+fn count_valid_passports(entries: &[PassportEntry], rules: &Ruleset) -> (i32, i32) {
     let mut valid_naive_entries = 0;
     let mut valid_entries = 0;
 
-    let passport_entries = read_entries(4, "")?;
-    for entry in &passport_entries {
-        let mut is_valid_props = true;
-        let mut is_valid_values = true;
-        for &field in &REQUIRED_FIELDS {
-            if let Some(value) = entry.get(field) {
-                if is_valid_values {
-                    is_valid_values = match field {
-                        "byr" => check_birth_year(value),
-                        "iyr" => check_issue_year(value),
-                        "eyr" => check_expiration_year(value),
-                        "hgt" => check_height(value),
-                        "hcl" => check_hair_color(value),
-                        "ecl" => check_eye_color(value),
-                        "pid" => check_passport_id(value),
-                        _ => true,
-                    };
-                }
-            } else {
-                is_valid_props = false;
-                is_valid_values = false;
-                break;
-            }
-        }
-        if is_valid_props {
+    for entry in entries {
+        if rules.has_required_fields(&entry.fields) {
             valid_naive_entries += 1
         }
-        if is_valid_values {
+        if rules.is_valid(&entry.fields) {
             valid_entries += 1;
         }
     }
 
-    return Ok((valid_naive_entries, valid_entries));
+    (valid_naive_entries, valid_entries)
 }
 
-fn check_num_in_range(start: i32, end: i32, value: &str) -> bool {
-    match value.parse::<i32>() {
-        Ok(value) => value >= start && value <= end,
-        Err(_) => false,
-    }
-}
-
-fn check_birth_year(birth_year: &str) -> bool {
-    check_num_in_range(1920, 2002, birth_year)
-}
-
-fn check_issue_year(issue_year: &str) -> bool {
-    check_num_in_range(2010, 2020, issue_year)
-}
-
-fn check_expiration_year(expiration_year: &str) -> bool {
-    check_num_in_range(2020, 2030, expiration_year)
-}
-
-fn check_height(height: &str) -> bool {
-    let unit_idx = height.len() - 2;
-    let valid_hgt_range = match &height[unit_idx..] {
-        "cm" => (150, 193),
-        "in" => (59, 76),
-        _ => return false,
-    };
-
-    check_num_in_range(valid_hgt_range.0, valid_hgt_range.1, &height[..unit_idx])
-}
-
-fn check_eye_color(eye_color: &str) -> bool {
-    ["amb", "blu", "brn", "gry", "grn", "hzl", "oth"].contains(&eye_color)
-}
-
-fn check_hair_color(hair_color: &str) -> bool {
-    lazy_static! {
-        static ref RE: Regex = Regex::new(r"^#[0-9a-f]{6}$").unwrap();
-    }
-    RE.is_match(hair_color)
-}
-
-fn check_passport_id(passport_id: &str) -> bool {
-    lazy_static! {
-        static ref RE: Regex = Regex::new(r"^\d{9}$").unwrap();
-    }
-    RE.is_match(passport_id)
-}
-
-fn day5() -> Result<(i32, i32), Box<dyn Error>> {
-    let get_seat_id = |(r, c): &(i32, i32)| r * 8 + c;
-
-    let seats: Vec<_> = read_lines(5)?
-        .iter()
-        .map(|p| check_boarding_pass(p, 128, 8))
-        .collect();
-
-    let max_seat = match seats.iter().map(get_seat_id).max() {
-        Some(x) => x,
-        None => -1,
-    };
+fn day5() -> Result<(u32, u32), Box<dyn Error>> {
+    let seat_map = SeatMap::new(SeatCodec::new(128, 8)?, &read_lines(5)?)?;
 
-    let missing_seat = find_missing_seat(seats, &get_seat_id);
+    let max_seat = seat_map.max_seat_id().ok_or("no boarding passes")?;
+    let missing_seat = *seat_map
+        .candidate_seats()
+        .first()
+        .ok_or("no free seat between two taken ones")?;
 
     Ok((max_seat, missing_seat))
 }
 
-fn check_boarding_pass(boarding_pass: &str, num_rows: i32, num_cols: i32) -> (i32, i32) {
-    let row_part_len = (num_cols - 1) as usize;
-    (
-        find_seat_row(&boarding_pass[0..row_part_len], num_rows),
-        find_seat_col(&boarding_pass[row_part_len..], num_cols),
-    )
-}
-
-fn seat_binary_search(directions: &str, lower_half_ind: char, size: i32) -> i32 {
-    let (mut first, mut last, mut mid) = (0, size - 1, size / 2 - 1);
-
-    for dir in directions.chars() {
-        if dir == lower_half_ind {
-            last = mid;
-        } else {
-            first = mid + 1;
-        }
-        mid = (last - first) / 2 + first;
-    }
-
-    mid
-}
-
-fn find_seat_row(row_part: &str, num_rows: i32) -> i32 {
-    seat_binary_search(row_part, 'F', num_rows)
-}
-
-fn find_seat_col(col_part: &str, num_cols: i32) -> i32 {
-    seat_binary_search(col_part, 'L', num_cols)
-}
-
-fn find_missing_seat<F>(seats: Vec<(i32, i32)>, id_func: F) -> i32
-where
-    F: Fn(&(i32, i32)) -> i32,
-{
-    let mut seat_ids: Vec<_> = seats.iter().map(&id_func).collect();
-    seat_ids.sort();
-
-    let mut expected_cur_seat_id = seat_ids[0];
-    for seat_id in seat_ids {
-        if expected_cur_seat_id == seat_id {
-            expected_cur_seat_id += 1;
-        } else {
-            return expected_cur_seat_id;
-        }
-    }
-    expected_cur_seat_id + 1
-}
-
 fn day6() -> Result<(i32, i32), Box<dyn Error>> {
-    let lines = read_lines(6)?;
-    let mut group_answers = Vec::new();
+    let groups = read_groups()?;
 
-    let mut cur_group = HashMap::new();
-    let mut group_size = 0;
-    for line in lines {
-        if line.is_empty() {
-            group_answers.push((group_size, cur_group));
-            cur_group = HashMap::new();
-            group_size = 0;
-        } else {
-            group_size += 1;
-            for c in line.chars() {
-                let counter = cur_group.entry(c).or_insert(0);
-                *counter += 1;
-            }
-        }
-    }
-    if !cur_group.is_empty() {
-        group_answers.push((group_size, cur_group));
-    }
-
-    let total_uniq_qs_per_group: usize = group_answers.iter().map(|(_, m)| m.len()).sum();
-    let total_univ_qs_per_group: usize = group_answers
-        .iter()
-        .map(|(size, counts)| {
-            counts
-                .iter()
-                .filter(|&(_, v)| v == size)
-                .map(|(k, _)| k)
-                .count()
-        })
-        .sum();
+    let total_uniq_qs_per_group: usize = groups.iter().map(|g| g.union().len()).sum();
+    let total_univ_qs_per_group: usize = groups.iter().map(|g| g.intersection().len()).sum();
 
     Ok((
         total_uniq_qs_per_group as i32,
@@ -450,151 +1062,70 @@ fn day6() -> Result<(i32, i32), Box<dyn Error>> {
     ))
 }
 
-fn day7() -> Result<(i32, i32), Box<dyn Error>> {
-    let lines = read_lines(7)?;
-
-    let mut contains: HashMap<String, HashMap<String, i32>> = HashMap::new();
-    let mut contained_by: HashMap<String, Vec<String>> = HashMap::new();
-
-    for line in lines {
-        let bags: Vec<&str> = line.trim_end_matches('.').split(" bags contain ").collect();
-        if bags[1] != "no other bags" {
-            let containing_bag = bags[0];
-            let contained_bags = bags[1].split(',').fold(HashMap::new(), |mut acc, s| {
-                let bag_props: Vec<&str> = s.trim().split_ascii_whitespace().collect();
-                let mut bag_color = String::from(bag_props[1]);
-                bag_color.push_str(" ");
-                bag_color.push_str(bag_props[2]);
-                acc.insert(bag_color, bag_props[0].parse::<i32>().unwrap());
-                acc
-            });
-
-            for bag in contained_bags.keys() {
-                let v = contained_by.entry(String::from(bag)).or_insert(Vec::new());
-                v.push(String::from(containing_bag));
-            }
-            contains.insert(String::from(containing_bag), contained_bags);
-        }
-    }
-    return Ok((
-        count_reachable_nodes("shiny gold", &contained_by) as i32,
-        count_inner_bags("shiny gold", &contains),
-    ));
-}
-
-fn count_reachable_nodes(start: &str, graph: &HashMap<String, Vec<String>>) -> usize {
-    let mut stack = vec![start];
-    let mut outermost_colors = HashSet::new();
-
-    let empty_neighbors = Vec::new();
-    while !stack.is_empty() {
-        let top = stack.pop().unwrap();
-        let neighbors = match graph.get(top) {
-            Some(neighbors) => neighbors,
-            None => &empty_neighbors,
-        };
-        for n in neighbors {
-            stack.push(n);
-            outermost_colors.insert(n);
-        }
-    }
-    outermost_colors.len()
+fn read_groups() -> Result<Vec<Group>, Box<dyn Error>> {
+    let lines = read_lines(6)?;
+    let groups = read_records(&lines)
+        .iter()
+        .map(|record| record.parse())
+        .collect::<Result<_, _>>()?;
+    Ok(groups)
 }
 
-fn count_inner_bags(start: &str, graph: &HashMap<String, HashMap<String, i32>>) -> i32 {
-    // println!("{:#?}", graph);
-    match graph.get(start) {
-        Some(nested) => {
-            let mut total = 0;
-            for b in nested.keys() {
-                let c = nested.get(b).unwrap();
-                total += c + c * count_inner_bags(b, graph);
-            }
-            total
-        }
-        None => 0,
-    }
+fn day7() -> Result<(u64, Count), Box<dyn Error>> {
+    let contains = parse_bag_rules(&read_lines(7)?)?;
+
+    Ok((
+        count_reachable_nodes("shiny gold", &contains) as u64,
+        count_inner_bags("shiny gold", &contains)?,
+    ))
 }
 
 fn day8() -> Result<(i32, i32), Box<dyn Error>> {
-    let lines = read_lines(8)?;
-    let program: Vec<(&str, i32)> = lines
-        .iter()
-        .map(|l| {
-            let inst: Vec<&str> = l.split_ascii_whitespace().collect();
-            (inst[0], inst[1].parse().unwrap())
-        })
-        .collect();
+    let program = read_program(8)?;
 
     Ok((find_infinite_loop(&program).0, fix_program(&program)))
 }
 
-fn find_infinite_loop(program: &[(&str, i32)]) -> (i32, bool) {
-    let program_length = program.len() as i32;
-
-    let mut i = 0;
-    let mut found_loop = false;
-    let mut visited = HashSet::new();
-    let mut accumulator = 0;
-
-    while !found_loop && i < program_length && i >= 0 {
-        if visited.contains(&i) {
-            found_loop = true
-        } else {
-            visited.insert(i);
-            let (inst, n) = &program[i as usize];
-            match inst.as_ref() {
-                "acc" => {
-                    i += 1;
-                    accumulator += n;
-                }
-                "jmp" => i += n,
-                _ => i += 1, // nop
-            }
-        }
-    }
+fn read_program(day: i32) -> Result<Vec<Instruction>, Box<dyn Error>> {
+    read_lines(day)?.iter().map(|l| l.parse()).collect()
+}
 
-    (accumulator, found_loop)
+fn find_infinite_loop(program: &[Instruction]) -> (i32, bool) {
+    let mut machine = Machine::new(program);
+    let status = machine.run();
+    (machine.acc(), status == Status::Looped)
 }
 
-fn fix_program(program: &[(&str, i32)]) -> i32 {
-    let mut broken = true;
-    let mut line = 0;
-    let mut acc = 0;
+fn fix_program(program: &[Instruction]) -> i32 {
+    let mut machine = Machine::new(program);
 
-    let mut prgm = Vec::new();
-    for line in program {
-        prgm.push((line.0, line.1));
-    }
-
-    while broken && line < program.len() {
-        let mut run = false;
-        let (orig_inst, value) = program[line];
-        let new_inst = match orig_inst {
-            "jmp" => {
-                run = true;
-                "nop"
-            }
-            "nop" => {
-                run = true;
-                "jmp"
-            }
-            _ => orig_inst,
+    // Only instructions on the original path can matter, so walk it once and try each patch
+    // from the point where execution would first diverge.
+    while machine.status() == Status::Running {
+        let line = machine.ip() as usize;
+        let inst = program[line];
+        let swapped = match inst.op {
+            Operation::Jmp => Some(Operation::Nop),
+            Operation::Nop => Some(Operation::Jmp),
+            Operation::Acc => None,
         };
-        if run {
-            prgm[line] = (new_inst, value);
-            let result = find_infinite_loop(&prgm);
-            acc = result.0;
-            broken = result.1;
-            prgm[line] = (orig_inst, value);
+        if let Some(op) = swapped {
+            let mut attempt = machine.fork();
+            attempt.patch(line, Instruction { op, arg: inst.arg });
+            if attempt.run() == Status::Terminated {
+                return attempt.acc();
+            }
         }
-        line += 1;
+        machine.step();
     }
-    acc
+    machine.acc()
 }
 
-fn day9() -> Result<(i64, i64), Box<dyn Error>> {
-    let lines: Vec<i64> = read_lines(9)?.iter().map(|l| l.parse().unwrap()).collect();
+/// Part 2 is an `i128` rather than a `Count` because XMAS numbers can be negative. It can't
+/// overflow: the weakness is the sum of two `i64`s, and the range sums behind it are at most
+/// one `i64` per number in the input, far from the `i128` limits.
+fn day9() -> Result<(i64, i128), Box<dyn Error>> {
+    let lines: Vec<i64> = read_numbers(9)?;
 
     Ok((
         find_first_invalid_xmas(&lines, 25),
@@ -602,84 +1133,32 @@ fn day9() -> Result<(i64, i64), Box<dyn Error>> {
     ))
 }
 
-fn find_first_invalid_xmas(data: &Vec<i64>, window_length: usize) -> i64 {
-    let mut start = 0;
-    let mut window = &data[start..window_length];
-    let mut result = 0;
-
-    for i in window_length..data.len() {
-        if !validate_next_xmas(window, data[i]) {
-            result = data[i];
-            break;
-        }
-        start += 1;
-        window = &data[start..start + window_length];
-    }
-    result
-}
-
-fn validate_next_xmas(window: &[i64], target: i64) -> bool {
-    two_sum(window, target) != (-1, -1)
-}
-
-fn find_weakness_xmas(data: &Vec<i64>, window_length: usize) -> i64 {
-    let invalid = find_first_invalid_xmas(&data, window_length);
-    let mut result = 0;
-
-    for i in 0..data.len() {
-        let start = i;
-        let mut j = i;
-        let mut sum = 0;
-        while sum < invalid {
-            sum += data[j];
-            j += 1;
-        }
-        if sum == invalid {
-            let block = &data[start..j];
-            let max = block.iter().max().unwrap();
-            let min = block.iter().min().unwrap();
-            result = min + max;
-            break;
-        }
+fn find_first_invalid_xmas(data: &[i64], window_length: usize) -> i64 {
+    match invalid_numbers(data.iter().copied(), window_length).next() {
+        Some((_, n)) => n,
+        None => 0,
     }
-    result
 }
 
-fn day10() -> Result<(i32, i32), Box<dyn Error>> {
-    let data: Vec<i32> = read_lines(10)?.iter().map(|d| d.parse().unwrap()).collect();
-
-    let joltage_diffs = calc_joltage_diffs(&data);
-    Ok((joltage_diffs.0 * joltage_diffs.2, NOT_IMPL as i32))
+fn find_weakness_xmas(data: &[i64], window_length: usize) -> i128 {
+    let invalid = find_first_invalid_xmas(data, window_length);
+    find_contiguous_range(data, invalid)
+        .and_then(|range| weakness(&data[range]))
+        .unwrap_or(0)
 }
 
-fn calc_joltage_diffs(joltages: &[i32]) -> (i32, i32, i32) {
-    let joltage_chain = make_full_joltage_chain(joltages);
+fn day10() -> Result<(i32, Count), Box<dyn Error>> {
+    let data: Vec<i32> = read_numbers(10)?;
 
-    let mut ones = 0;
-    let mut twos = 0;
-    let mut threes = 1; // plus the 3 at the end
-
-    let mut cur_joltage = 0;
-
-    for j in joltage_chain {
-        match j - cur_joltage {
-            1 => ones += 1,
-            2 => twos += 1,
-            _ => threes += 1,
-        };
-        cur_joltage = j;
-    }
-
-    (ones, twos, threes)
-}
-
-fn make_full_joltage_chain(joltages: &[i32]) -> Vec<i32> {
-    let mut copy = Vec::new();
-    for j in joltages {
-        copy.push(*j);
-    }
-    copy.sort();
-    copy
+    let rules = JoltageRules::default();
+    let chain = rules.full_chain(&data);
+    let joltage_diffs = calc_joltage_diffs(&chain, &rules)?;
+    let count = |step| joltage_diffs.get(&step).copied().unwrap_or(0);
+    let part1 = count(1)
+        .checked_mul(count(3))
+        .ok_or("joltage difference product overflowed i32")?;
+    let part2 = count_arrangements(&chain, &rules)?;
+    Ok((part1, part2))
 }
 
 fn day11() -> Result<(i64, i64), Box<dyn Error>> {
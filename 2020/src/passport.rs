@@ -0,0 +1,785 @@
+use std::collections::{HashMap, HashSet};
+use std::error::Error;
+use std::fmt;
+use std::fs;
+use std::str::FromStr;
+
+use regex::Regex;
+use serde::{de, Deserialize, Deserializer, Serialize, Serializer};
+
+use crate::records::{read_records, FromRecord, Record, RecordError};
+
+/// The rules from the puzzle, also kept in `rules/passports.json` as a starting point for
+/// other policies.
+const DEFAULT_RULES: &str = include_str!("../rules/passports.json");
+
+#[derive(Deserialize)]
+struct RulesetSpec {
+    fields: Vec<FieldSpec>,
+}
+
+#[derive(Deserialize)]
+struct FieldSpec {
+    name: String,
+    #[serde(default = "required_by_default")]
+    required: bool,
+    #[serde(default)]
+    rule: Option<RuleSpec>,
+}
+
+fn required_by_default() -> bool {
+    true
+}
+
+#[derive(Deserialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+enum RuleSpec {
+    IntRange { min: i64, max: i64 },
+    UnitRange { units: Vec<UnitSpec> },
+    Regex { pattern: String },
+    Enum { values: Vec<String> },
+}
+
+#[derive(Deserialize)]
+struct UnitSpec {
+    unit: String,
+    min: i64,
+    max: i64,
+}
+
+/// What a field's value has to look like.
+#[derive(Debug)]
+pub enum Rule {
+    /// A whole number between `min` and `max`, inclusive.
+    IntRange {
+        min: i64,
+        max: i64,
+    },
+    /// A whole number followed by one of the units, within that unit's range.
+    UnitRange {
+        units: Vec<(String, i64, i64)>,
+    },
+    Regex(Regex),
+    Enum(Vec<String>),
+}
+
+impl Rule {
+    fn from_spec(spec: RuleSpec) -> Result<Rule, Box<dyn Error>> {
+        Ok(match spec {
+            RuleSpec::IntRange { min, max } => Rule::IntRange { min, max },
+            RuleSpec::UnitRange { units } => Rule::UnitRange {
+                units: units.into_iter().map(|u| (u.unit, u.min, u.max)).collect(),
+            },
+            RuleSpec::Regex { pattern } => Rule::Regex(Regex::new(&pattern)?),
+            RuleSpec::Enum { values } => Rule::Enum(values),
+        })
+    }
+
+    /// Checks `value` against the rule, explaining what's wrong with it if it doesn't pass.
+    pub fn check(&self, value: &str) -> Result<(), String> {
+        match self {
+            Rule::IntRange { min, max } => check_num_in_range(*min, *max, value),
+            Rule::UnitRange { units } => {
+                // the longest unit wins, so `m` listed before `cm` doesn't read `150cm` as
+                // `150c` metres
+                let matching = units
+                    .iter()
+                    .filter(|(unit, _, _)| value.ends_with(unit.as_str()))
+                    .max_by_key(|(unit, _, _)| unit.len());
+                if let Some((unit, min, max)) = matching {
+                    return check_num_in_range(*min, *max, &value[..value.len() - unit.len()]);
+                }
+                let names: Vec<&str> = units.iter().map(|(unit, _, _)| unit.as_str()).collect();
+                Err(format!("unit must be one of {}", names.join(", ")))
+            }
+            Rule::Regex(re) => {
+                if re.is_match(value) {
+                    Ok(())
+                } else {
+                    Err(format!("doesn't match {}", re))
+                }
+            }
+            Rule::Enum(values) => {
+                if values.iter().any(|v| v == value) {
+                    Ok(())
+                } else {
+                    Err(format!("not one of {}", values.join(", ")))
+                }
+            }
+        }
+    }
+}
+
+fn check_num_in_range(min: i64, max: i64, value: &str) -> Result<(), String> {
+    match value.parse::<i64>() {
+        Ok(n) if n >= min && n <= max => Ok(()),
+        Ok(_) => Err(format!("out of range {}..={}", min, max)),
+        Err(_) => Err(String::from("not a number")),
+    }
+}
+
+#[derive(Debug)]
+pub struct FieldRule {
+    pub name: String,
+    pub required: bool,
+    pub rule: Option<Rule>,
+}
+
+/// The fields a passport is checked for, loaded from JSON like `rules/passports.json`.
+#[derive(Debug)]
+pub struct Ruleset {
+    pub fields: Vec<FieldRule>,
+}
+
+impl Ruleset {
+    pub fn from_json(json: &str) -> Result<Ruleset, Box<dyn Error>> {
+        let spec: RulesetSpec = serde_json::from_str(json)?;
+        let fields = spec
+            .fields
+            .into_iter()
+            .map(|f| {
+                let FieldSpec {
+                    name,
+                    required,
+                    rule,
+                } = f;
+                let rule = match rule {
+                    Some(rule) => Some(
+                        Rule::from_spec(rule).map_err(|e| format!("rule for {}: {}", name, e))?,
+                    ),
+                    None => None,
+                };
+                Ok(FieldRule {
+                    name,
+                    required,
+                    rule,
+                })
+            })
+            .collect::<Result<_, Box<dyn Error>>>()?;
+        Ok(Ruleset { fields })
+    }
+
+    pub fn load(path: &str) -> Result<Ruleset, Box<dyn Error>> {
+        Ruleset::from_json(&fs::read_to_string(path)?)
+            .map_err(|e| format!("{}: {}", path, e).into())
+    }
+
+    pub fn field(&self, name: &str) -> Option<&FieldRule> {
+        self.fields.iter().find(|f| f.name == name)
+    }
+
+    /// Lists the required fields the passport is missing and the values that break their rule.
+    pub fn report(&self, passport: &PassportEntry) -> PassportReport {
+        let mut missing = Vec::new();
+        let mut invalid = Vec::new();
+        for field in &self.fields {
+            match (passport.fields.get(&field.name), &field.rule) {
+                (None, _) if field.required => missing.push(field.name.clone()),
+                (Some(value), Some(rule)) => {
+                    if let Err(reason) = rule.check(value) {
+                        invalid.push(FieldFailure {
+                            field: field.name.clone(),
+                            value: value.clone(),
+                            reason,
+                        });
+                    }
+                }
+                _ => {}
+            }
+        }
+        PassportReport {
+            line: passport.line,
+            valid: missing.is_empty() && invalid.is_empty(),
+            missing,
+            invalid,
+        }
+    }
+
+    /// Whether every required field is there, whatever its value.
+    pub fn has_required_fields(&self, entry: &HashMap<String, String>) -> bool {
+        self.fields
+            .iter()
+            .all(|f| !f.required || entry.contains_key(&f.name))
+    }
+
+    /// Whether every required field is there and every field that's there follows its rule.
+    pub fn is_valid(&self, entry: &HashMap<String, String>) -> bool {
+        self.has_required_fields(entry)
+            && self
+                .fields
+                .iter()
+                .all(|f| match (&f.rule, entry.get(&f.name)) {
+                    (Some(rule), Some(value)) => rule.check(value).is_ok(),
+                    _ => true,
+                })
+    }
+}
+
+impl Default for Ruleset {
+    fn default() -> Self {
+        Ruleset::from_json(DEFAULT_RULES).expect("default passport rules should parse")
+    }
+}
+
+/// One passport's fields, along with the line in the input where it starts.
+#[derive(Debug)]
+pub struct PassportEntry {
+    pub line: usize,
+    pub fields: HashMap<String, String>,
+}
+
+fn duplicate_field(record: &Record, line: usize, column: usize, key: &str) -> RecordError {
+    RecordError {
+        line,
+        column,
+        message: format!(
+            "{} was already given for the passport starting on line {}",
+            key, record.line
+        ),
+    }
+}
+
+/// Splits the batch file into passports. Passports are separated by blank lines and their
+/// `key:value` fields by whitespace. Every key has to be one the ruleset knows about, and
+/// can only show up once per passport.
+pub fn read_passports(
+    lines: &[String],
+    rules: &Ruleset,
+) -> Result<Vec<PassportEntry>, RecordError> {
+    let mut passports = Vec::new();
+    for record in read_records(lines) {
+        let mut fields = HashMap::new();
+        for (line, column, key, value) in record.key_values()? {
+            if rules.field(key).is_none() {
+                return Err(RecordError {
+                    line,
+                    column,
+                    message: format!("unknown field {:?}", key),
+                });
+            }
+            if fields.contains_key(key) {
+                return Err(duplicate_field(&record, line, column, key));
+            }
+            fields.insert(String::from(key), String::from(value));
+        }
+        passports.push(PassportEntry {
+            line: record.line,
+            fields,
+        });
+    }
+    Ok(passports)
+}
+
+#[derive(Debug, Serialize)]
+pub struct FieldFailure {
+    pub field: String,
+    pub value: String,
+    pub reason: String,
+}
+
+impl fmt::Display for FieldFailure {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}={}: {}", self.field, self.value, self.reason)
+    }
+}
+
+/// Why a passport did or didn't pass a ruleset.
+#[derive(Debug, Serialize)]
+pub struct PassportReport {
+    pub line: usize,
+    pub valid: bool,
+    pub missing: Vec<String>,
+    pub invalid: Vec<FieldFailure>,
+}
+
+impl fmt::Display for PassportReport {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "line {}: ", self.line)?;
+        if self.valid {
+            return write!(f, "valid");
+        }
+        let mut problems = Vec::new();
+        if !self.missing.is_empty() {
+            problems.push(format!("missing {}", self.missing.join(", ")));
+        }
+        problems.extend(self.invalid.iter().map(|failure| failure.to_string()));
+        write!(f, "{}", problems.join("; "))
+    }
+}
+
+/// A field as written in the batch file, typed when it fits the type exactly and kept as the
+/// original text otherwise, so a round trip through JSON or CSV doesn't lose or fix anything.
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(untagged)]
+pub enum Value<T> {
+    Typed(T),
+    Raw(String),
+}
+
+impl<T: FromStr + fmt::Display> Value<T> {
+    fn parse(s: &str) -> Value<T> {
+        match s.parse::<T>() {
+            Ok(value) if value.to_string() == s => Value::Typed(value),
+            _ => Value::Raw(String::from(s)),
+        }
+    }
+}
+
+impl<T: fmt::Display> fmt::Display for Value<T> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Value::Typed(value) => value.fmt(f),
+            Value::Raw(s) => f.write_str(s),
+        }
+    }
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum HeightUnit {
+    Cm,
+    In,
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Height {
+    pub value: u16,
+    pub unit: HeightUnit,
+}
+
+impl FromStr for Height {
+    type Err = Box<dyn Error>;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (value, unit) = if let Some(value) = s.strip_suffix("cm") {
+            (value, HeightUnit::Cm)
+        } else if let Some(value) = s.strip_suffix("in") {
+            (value, HeightUnit::In)
+        } else {
+            return Err(format!("height without a unit: {:?}", s).into());
+        };
+        Ok(Height {
+            value: value.parse()?,
+            unit,
+        })
+    }
+}
+
+impl fmt::Display for Height {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let unit = match self.unit {
+            HeightUnit::Cm => "cm",
+            HeightUnit::In => "in",
+        };
+        write!(f, "{}{}", self.value, unit)
+    }
+}
+
+/// A `#rrggbb` color, written out in lowercase.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct HexColor(pub u32);
+
+impl FromStr for HexColor {
+    type Err = Box<dyn Error>;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.strip_prefix('#') {
+            Some(hex) if hex.len() == 6 && hex.bytes().all(|b| b.is_ascii_hexdigit()) => {
+                Ok(HexColor(u32::from_str_radix(hex, 16)?))
+            }
+            _ => Err(format!("not a #rrggbb color: {:?}", s).into()),
+        }
+    }
+}
+
+impl fmt::Display for HexColor {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "#{:06x}", self.0)
+    }
+}
+
+impl Serialize for HexColor {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_str(self)
+    }
+}
+
+impl<'de> Deserialize<'de> for HexColor {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let s = String::deserialize(deserializer)?;
+        match s.parse::<HexColor>() {
+            Ok(color) if color.to_string() == s => Ok(color),
+            _ => Err(de::Error::custom(format!("not a #rrggbb color: {:?}", s))),
+        }
+    }
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum EyeColor {
+    Amb,
+    Blu,
+    Brn,
+    Gry,
+    Grn,
+    Hzl,
+    Oth,
+}
+
+const EYE_COLORS: [(EyeColor, &str); 7] = [
+    (EyeColor::Amb, "amb"),
+    (EyeColor::Blu, "blu"),
+    (EyeColor::Brn, "brn"),
+    (EyeColor::Gry, "gry"),
+    (EyeColor::Grn, "grn"),
+    (EyeColor::Hzl, "hzl"),
+    (EyeColor::Oth, "oth"),
+];
+
+impl FromStr for EyeColor {
+    type Err = Box<dyn Error>;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        EYE_COLORS
+            .iter()
+            .find(|&&(_, name)| name == s)
+            .map(|&(color, _)| color)
+            .ok_or_else(|| format!("unknown eye color: {:?}", s).into())
+    }
+}
+
+impl fmt::Display for EyeColor {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let &(_, name) = EYE_COLORS.iter().find(|&&(c, _)| c == *self).unwrap();
+        f.write_str(name)
+    }
+}
+
+/// The fields a `Passport` has, in the order they're written to CSV.
+const PASSPORT_FIELDS: [&str; 8] = ["byr", "iyr", "eyr", "hgt", "hcl", "ecl", "pid", "cid"];
+
+/// A passport with typed fields, for moving the batch to and from JSON and CSV.
+#[derive(Debug, Default, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Passport {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub byr: Option<Value<u16>>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub iyr: Option<Value<u16>>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub eyr: Option<Value<u16>>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub hgt: Option<Value<Height>>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub hcl: Option<Value<HexColor>>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub ecl: Option<Value<EyeColor>>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub pid: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub cid: Option<String>,
+}
+
+impl Passport {
+    /// Types the fields read from the batch file. Fails on fields a `Passport` doesn't have,
+    /// which only a custom ruleset lets through.
+    pub fn from_fields(fields: &HashMap<String, String>) -> Result<Passport, Box<dyn Error>> {
+        let mut passport = Passport::default();
+        for (key, value) in fields {
+            passport.set(key, value)?;
+        }
+        Ok(passport)
+    }
+
+    fn set(&mut self, key: &str, value: &str) -> Result<(), String> {
+        match key {
+            "byr" => self.byr = Some(Value::parse(value)),
+            "iyr" => self.iyr = Some(Value::parse(value)),
+            "eyr" => self.eyr = Some(Value::parse(value)),
+            "hgt" => self.hgt = Some(Value::parse(value)),
+            "hcl" => self.hcl = Some(Value::parse(value)),
+            "ecl" => self.ecl = Some(Value::parse(value)),
+            "pid" => self.pid = Some(String::from(value)),
+            "cid" => self.cid = Some(String::from(value)),
+            other => return Err(format!("passports have no {} field", other)),
+        }
+        Ok(())
+    }
+
+    /// The fields as they'd be written in the batch file, for checking against a ruleset.
+    pub fn to_fields(&self) -> HashMap<String, String> {
+        let values = [
+            self.byr.as_ref().map(Value::to_string),
+            self.iyr.as_ref().map(Value::to_string),
+            self.eyr.as_ref().map(Value::to_string),
+            self.hgt.as_ref().map(Value::to_string),
+            self.hcl.as_ref().map(Value::to_string),
+            self.ecl.as_ref().map(Value::to_string),
+            self.pid.clone(),
+            self.cid.clone(),
+        ];
+        PASSPORT_FIELDS
+            .iter()
+            .zip(values)
+            .filter_map(|(&key, value)| Some((String::from(key), value?)))
+            .collect()
+    }
+}
+
+impl FromRecord for Passport {
+    fn from_record(record: &Record) -> Result<Self, RecordError> {
+        let mut passport = Passport::default();
+        let mut seen = HashSet::new();
+        for (line, column, key, value) in record.key_values()? {
+            if !seen.insert(key) {
+                return Err(duplicate_field(record, line, column, key));
+            }
+            passport.set(key, value).map_err(|message| RecordError {
+                line,
+                column,
+                message,
+            })?;
+        }
+        Ok(passport)
+    }
+}
+
+pub fn passports_to_csv(passports: &[Passport]) -> Result<String, Box<dyn Error>> {
+    let mut writer = csv::Writer::from_writer(Vec::new());
+    writer.write_record(PASSPORT_FIELDS)?;
+    for passport in passports {
+        let mut fields = passport.to_fields();
+        writer.write_record(
+            PASSPORT_FIELDS
+                .iter()
+                .map(|&key| fields.remove(key).unwrap_or_default()),
+        )?;
+    }
+    Ok(String::from_utf8(writer.into_inner()?)?)
+}
+
+/// Reads passports from CSV with a header row naming the fields. Empty cells are missing
+/// fields.
+pub fn passports_from_csv(data: &str) -> Result<Vec<Passport>, Box<dyn Error>> {
+    let mut reader = csv::Reader::from_reader(data.as_bytes());
+    let headers = reader.headers()?.clone();
+    let mut passports = Vec::new();
+    for record in reader.records() {
+        let record = record?;
+        let fields = headers
+            .iter()
+            .zip(record.iter())
+            .filter(|(_, value)| !value.is_empty())
+            .map(|(key, value)| (String::from(key), String::from(value)))
+            .collect();
+        passports.push(Passport::from_fields(&fields)?);
+    }
+    Ok(passports)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use proptest::prelude::*;
+
+    fn every_kind_of_rule() -> Vec<Rule> {
+        vec![
+            Rule::IntRange {
+                min: 1920,
+                max: 2002,
+            },
+            Rule::UnitRange {
+                units: vec![(String::from("cm"), 150, 193), (String::from("in"), 59, 76)],
+            },
+            Rule::Regex(Regex::new("^#[0-9a-f]{6}$").unwrap()),
+            Rule::Enum(vec![String::from("amb"), String::from("blu")]),
+        ]
+    }
+
+    fn read(batch: &str) -> Result<Vec<PassportEntry>, RecordError> {
+        let lines: Vec<String> = batch.lines().map(String::from).collect();
+        read_passports(&lines, &Ruleset::default())
+    }
+
+    fn parse(batch: &str) -> Result<Passport, RecordError> {
+        let lines: Vec<String> = batch.lines().map(String::from).collect();
+        read_records(&lines)[0].parse()
+    }
+
+    #[test]
+    fn rules_reject_odd_values_without_panicking() {
+        for value in [
+            "",
+            "c",
+            "n",
+            "cm",
+            "in",
+            "-",
+            "+",
+            ":",
+            "é",
+            "日本",
+            "1é",
+            "é cm",
+            "-5in",
+            "#",
+            "#12345é",
+            "99999999999999999999",
+            "99999999999999999999cm",
+        ] {
+            for rule in every_kind_of_rule() {
+                assert!(rule.check(value).is_err(), "{:?} passed {:?}", value, rule);
+            }
+        }
+    }
+
+    #[test]
+    fn rules_explain_failures() {
+        let height = &every_kind_of_rule()[1];
+        assert_eq!(height.check("60in"), Ok(()));
+        assert_eq!(
+            height.check("190in"),
+            Err(String::from("out of range 59..=76"))
+        );
+        assert_eq!(height.check("cm"), Err(String::from("not a number")));
+        assert_eq!(
+            height.check("1"),
+            Err(String::from("unit must be one of cm, in"))
+        );
+    }
+
+    #[test]
+    fn longest_unit_wins() {
+        let height = Rule::UnitRange {
+            units: vec![(String::from("m"), 1, 2), (String::from("cm"), 150, 193)],
+        };
+        assert_eq!(height.check("150cm"), Ok(()));
+        assert_eq!(height.check("2m"), Ok(()));
+        assert_eq!(
+            height.check("3cm"),
+            Err(String::from("out of range 150..=193"))
+        );
+    }
+
+    #[test]
+    fn passport_ids_are_ascii_digits() {
+        let rules = Ruleset::default();
+        let pid = rules.field("pid").unwrap().rule.as_ref().unwrap();
+        assert_eq!(pid.check("000000001"), Ok(()));
+        // `\d` would let these Arabic-Indic digits through
+        assert!(pid.check("١٢٣٤٥٦٧٨٩").is_err());
+    }
+
+    #[test]
+    fn duplicate_keys_point_at_the_repeat() {
+        let err = read("byr:1920\nhgt:60in byr:1921").unwrap_err();
+        assert_eq!((err.line, err.column), (2, 10));
+        assert_eq!(
+            err.message,
+            "byr was already given for the passport starting on line 1"
+        );
+    }
+
+    #[test]
+    fn duplicate_keys_are_only_checked_within_a_passport() {
+        assert_eq!(read("byr:1920\n\nbyr:1921").unwrap().len(), 2);
+    }
+
+    #[test]
+    fn unknown_keys_point_at_the_key() {
+        let err = read("byr:1920\n\n\nhgt:60in  foo:1").unwrap_err();
+        assert_eq!((err.line, err.column), (4, 11));
+        assert_eq!(err.message, "unknown field \"foo\"");
+    }
+
+    #[test]
+    fn malformed_fields_point_at_the_field() {
+        for (batch, line, column) in [
+            ("byr", 1, 1),
+            ("byr:1920\nhgt:60in pid", 2, 10),
+            ("hcl:é日 ecl:", 1, 8),
+            ("byr:1920 :5", 1, 10),
+        ] {
+            let err = read(batch).unwrap_err();
+            assert_eq!((err.line, err.column), (line, column), "{:?}", batch);
+            assert!(err.message.starts_with("expected key:value"), "{:?}", batch);
+        }
+    }
+
+    #[test]
+    fn passports_remember_their_first_line() {
+        let passports = read("\nbyr:1920\niyr:2010\n\n\ncid:1").unwrap();
+        let starts: Vec<usize> = passports.iter().map(|p| p.line).collect();
+        assert_eq!(starts, vec![2, 6]);
+    }
+
+    #[test]
+    fn passports_type_the_values_that_fit() {
+        let passport = parse("byr:1937 hgt:183cm\nhcl:#fffffd ecl:gry pid:020960146").unwrap();
+        assert!(matches!(passport.byr, Some(Value::Typed(1937))));
+        assert!(matches!(
+            passport.hgt,
+            Some(Value::Typed(Height {
+                value: 183,
+                unit: HeightUnit::Cm
+            }))
+        ));
+        assert!(matches!(
+            passport.hcl,
+            Some(Value::Typed(HexColor(0xfffffd)))
+        ));
+        assert!(matches!(passport.ecl, Some(Value::Typed(EyeColor::Gry))));
+        assert_eq!(passport.pid.as_deref(), Some("020960146"));
+        assert!(passport.iyr.is_none());
+    }
+
+    #[test]
+    fn passports_keep_values_that_dont_fit_as_written() {
+        let passport = parse("byr:01937 hgt:183 hcl:#FFFFFD ecl:utc").unwrap();
+        for (key, value) in [
+            ("byr", "01937"),
+            ("hgt", "183"),
+            ("hcl", "#FFFFFD"),
+            ("ecl", "utc"),
+        ] {
+            assert_eq!(passport.to_fields()[key], value);
+        }
+        assert!(matches!(passport.byr, Some(Value::Raw(_))));
+        assert!(matches!(passport.hcl, Some(Value::Raw(_))));
+    }
+
+    #[test]
+    fn passports_reject_fields_they_dont_have() {
+        let err = parse("byr:1937\n  foo:1").unwrap_err();
+        assert_eq!((err.line, err.column), (2, 3));
+        assert_eq!(err.message, "passports have no foo field");
+    }
+
+    #[test]
+    fn passports_reject_duplicate_fields() {
+        let err = parse("ecl:gry\nbyr:1 ecl:amb").unwrap_err();
+        assert_eq!((err.line, err.column), (2, 7));
+        assert_eq!(
+            err.message,
+            "ecl was already given for the passport starting on line 1"
+        );
+    }
+
+    proptest! {
+        #[test]
+        fn rules_never_panic(value in any::<String>()) {
+            for rule in every_kind_of_rule() {
+                let _ = rule.check(&value);
+            }
+        }
+
+        #[test]
+        fn rules_never_panic_on_almost_valid_values(
+            value in "[-+#]?[0-9a-fé]{0,22}(cm|in|c|i|m|n)?"
+        ) {
+            for rule in every_kind_of_rule() {
+                let _ = rule.check(&value);
+            }
+        }
+    }
+}
@@ -0,0 +1,330 @@
+use std::collections::{BTreeMap, HashSet};
+use std::error::Error;
+use std::fmt;
+use std::str::FromStr;
+
+use regex::Regex;
+use serde::Serialize;
+
+/// The part of a line before the colon, like `1-3 a`. What the numbers mean is up to the
+/// policy reading them.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct PolicySpec {
+    pub low: usize,
+    pub high: usize,
+    pub letter: char,
+}
+
+impl fmt::Display for PolicySpec {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}-{} {}", self.low, self.high, self.letter)
+    }
+}
+
+impl FromStr for PolicySpec {
+    type Err = Box<dyn Error>;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let malformed = || format!("expected a policy like \"1-3 a\", found {:?}", s);
+        let words: Vec<&str> = s.split_ascii_whitespace().collect();
+        let (range, letter) = match words[..] {
+            [range, letter] => (range, letter),
+            _ => return Err(malformed().into()),
+        };
+        let (low, high) = range.split_once('-').ok_or_else(malformed)?;
+        let mut chars = letter.chars();
+        let letter = match (chars.next(), chars.next()) {
+            (Some(c), None) => c,
+            _ => return Err(malformed().into()),
+        };
+        Ok(PolicySpec {
+            low: low.parse().map_err(|_| malformed())?,
+            high: high.parse().map_err(|_| malformed())?,
+            letter,
+        })
+    }
+}
+
+/// One line of the password database.
+#[derive(Clone, Debug)]
+pub struct PasswordEntry {
+    pub line: usize,
+    pub policy: PolicySpec,
+    pub password: String,
+}
+
+/// Parses every line of the database as `<policy>: <password>`.
+pub fn parse_password_entries(lines: &[String]) -> Result<Vec<PasswordEntry>, Box<dyn Error>> {
+    lines
+        .iter()
+        .enumerate()
+        .map(|(i, line)| {
+            let (policy, password) = line
+                .split_once(':')
+                .ok_or_else(|| format!("line {}: expected <policy>: <password>", i + 1))?;
+            Ok(PasswordEntry {
+                line: i + 1,
+                policy: policy
+                    .parse()
+                    .map_err(|e| format!("line {}: {}", i + 1, e))?,
+                password: String::from(password.trim()),
+            })
+        })
+        .collect()
+}
+
+pub trait PasswordPolicy {
+    fn name(&self) -> String;
+
+    /// Checks the entry's password, explaining why it fails if it does.
+    fn check(&self, entry: &PasswordEntry) -> Result<(), String>;
+}
+
+/// The sled rental's policy: the letter has to appear between `low` and `high` times.
+pub struct SledPolicy;
+
+impl PasswordPolicy for SledPolicy {
+    fn name(&self) -> String {
+        String::from("sled")
+    }
+
+    fn check(&self, entry: &PasswordEntry) -> Result<(), String> {
+        let PolicySpec { low, high, letter } = entry.policy;
+        let count = entry.password.chars().filter(|&c| c == letter).count();
+        if count >= low && count <= high {
+            Ok(())
+        } else {
+            Err(format!(
+                "{:?} appears {} times, outside {}..={}",
+                letter, count, low, high
+            ))
+        }
+    }
+}
+
+/// The toboggan rental's policy: exactly one of the (one-based) positions `low` and `high`
+/// holds the letter. A position past the end of the password doesn't hold anything.
+pub struct TobogganPolicy;
+
+impl PasswordPolicy for TobogganPolicy {
+    fn name(&self) -> String {
+        String::from("toboggan")
+    }
+
+    fn check(&self, entry: &PasswordEntry) -> Result<(), String> {
+        let PolicySpec { low, high, letter } = entry.policy;
+        let holds = |pos: usize| {
+            pos.checked_sub(1)
+                .and_then(|i| entry.password.chars().nth(i))
+                == Some(letter)
+        };
+        match (holds(low), holds(high)) {
+            (true, false) | (false, true) => Ok(()),
+            (true, true) => Err(format!(
+                "both positions {} and {} are {:?}",
+                low, high, letter
+            )),
+            (false, false) => Err(format!(
+                "neither position {} nor {} is {:?}",
+                low, high, letter
+            )),
+        }
+    }
+}
+
+/// Ignores the line's own policy and requires the whole password to match a pattern.
+pub struct RegexPolicy {
+    pattern: String,
+    regex: Regex,
+}
+
+impl RegexPolicy {
+    pub fn new(pattern: &str) -> Result<RegexPolicy, regex::Error> {
+        // checked on its own first so errors point into the pattern as given
+        Regex::new(pattern)?;
+        Ok(RegexPolicy {
+            pattern: String::from(pattern),
+            regex: Regex::new(&format!("^(?:{})$", pattern))?,
+        })
+    }
+}
+
+impl PasswordPolicy for RegexPolicy {
+    fn name(&self) -> String {
+        format!("regex:{}", self.pattern)
+    }
+
+    fn check(&self, entry: &PasswordEntry) -> Result<(), String> {
+        if self.regex.is_match(&entry.password) {
+            Ok(())
+        } else {
+            Err(format!("doesn't match {}", self.pattern))
+        }
+    }
+}
+
+/// Ignores the line's own policy and requires at least some number of different characters.
+pub struct MinDistinctPolicy(usize);
+
+impl PasswordPolicy for MinDistinctPolicy {
+    fn name(&self) -> String {
+        format!("min-distinct:{}", self.0)
+    }
+
+    fn check(&self, entry: &PasswordEntry) -> Result<(), String> {
+        let distinct = entry.password.chars().collect::<HashSet<_>>().len();
+        if distinct >= self.0 {
+            Ok(())
+        } else {
+            Err(format!(
+                "{} distinct characters, fewer than {}",
+                distinct, self.0
+            ))
+        }
+    }
+}
+
+/// Builds a policy from the argument after the colon in `name:argument`, which is empty
+/// when there isn't one.
+pub type PolicyFactory = fn(&str) -> Result<Box<dyn PasswordPolicy>, Box<dyn Error>>;
+
+/// The policies that can be picked by name, like `--policy min-distinct:4`.
+pub struct PolicyRegistry {
+    policies: BTreeMap<String, (&'static str, PolicyFactory)>,
+}
+
+impl PolicyRegistry {
+    pub fn new() -> PolicyRegistry {
+        PolicyRegistry {
+            policies: BTreeMap::new(),
+        }
+    }
+
+    pub fn register(&mut self, name: &str, usage: &'static str, factory: PolicyFactory) {
+        self.policies.insert(String::from(name), (usage, factory));
+    }
+
+    /// The name and usage of every registered policy, in order of name.
+    pub fn policies(&self) -> impl Iterator<Item = (&str, &'static str)> {
+        self.policies
+            .iter()
+            .map(|(name, &(usage, _))| (name.as_str(), usage))
+    }
+
+    /// Builds the policy named by `spec`, which is `name` or `name:argument`.
+    pub fn create(&self, spec: &str) -> Result<Box<dyn PasswordPolicy>, Box<dyn Error>> {
+        let (name, arg) = spec.split_once(':').unwrap_or((spec, ""));
+        let (_, factory) = self.policies.get(name).ok_or_else(|| {
+            let names: Vec<&str> = self.policies.keys().map(String::as_str).collect();
+            format!(
+                "unknown policy {}; expected one of {}",
+                name,
+                names.join(", ")
+            )
+        })?;
+        factory(arg).map_err(|e| format!("policy {}: {}", spec, e).into())
+    }
+}
+
+impl Default for PolicyRegistry {
+    fn default() -> Self {
+        let mut registry = PolicyRegistry::new();
+        registry.register(
+            "sled",
+            "sled: the letter appears between low and high times",
+            |_| Ok(Box::new(SledPolicy)),
+        );
+        registry.register(
+            "toboggan",
+            "toboggan: exactly one of positions low and high is the letter",
+            |_| Ok(Box::new(TobogganPolicy)),
+        );
+        registry.register(
+            "regex",
+            "regex:<pattern>: the whole password matches the pattern",
+            |pattern| Ok(Box::new(RegexPolicy::new(pattern)?)),
+        );
+        registry.register(
+            "min-distinct",
+            "min-distinct:<n>: the password has at least n different characters",
+            |n| Ok(Box::new(MinDistinctPolicy(n.parse()?))),
+        );
+        registry
+    }
+}
+
+/// A line that failed a policy, and why.
+#[derive(Debug, Serialize)]
+pub struct AuditFailure {
+    pub line: usize,
+    /// The line's own rule, like `1-3 a`.
+    pub rule: String,
+    pub password: String,
+    pub reason: String,
+}
+
+impl fmt::Display for AuditFailure {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "line {}: {}: {}: {}",
+            self.line, self.rule, self.password, self.reason
+        )
+    }
+}
+
+#[derive(Debug, Serialize)]
+pub struct PolicyAudit {
+    pub policy: String,
+    pub passed: usize,
+    pub failed: usize,
+    pub failures: Vec<AuditFailure>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct AuditReport {
+    pub entries: usize,
+    /// Entries that pass every policy audited.
+    pub passed_all: usize,
+    /// Entries that fail every policy audited.
+    pub failed_all: usize,
+    pub policies: Vec<PolicyAudit>,
+}
+
+/// Checks every entry against every policy, keeping the reason for each failure.
+pub fn audit(entries: &[PasswordEntry], policies: &[Box<dyn PasswordPolicy>]) -> AuditReport {
+    let mut failures_per_entry = vec![0; entries.len()];
+    let audits = policies
+        .iter()
+        .map(|policy| {
+            let mut failures = Vec::new();
+            for (i, entry) in entries.iter().enumerate() {
+                if let Err(reason) = policy.check(entry) {
+                    failures_per_entry[i] += 1;
+                    failures.push(AuditFailure {
+                        line: entry.line,
+                        rule: entry.policy.to_string(),
+                        password: entry.password.clone(),
+                        reason,
+                    });
+                }
+            }
+            PolicyAudit {
+                policy: policy.name(),
+                passed: entries.len() - failures.len(),
+                failed: failures.len(),
+                failures,
+            }
+        })
+        .collect();
+
+    AuditReport {
+        entries: entries.len(),
+        passed_all: failures_per_entry.iter().filter(|&&n| n == 0).count(),
+        failed_all: failures_per_entry
+            .iter()
+            .filter(|&&n| n == policies.len())
+            .count(),
+        policies: audits,
+    }
+}
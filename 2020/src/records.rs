@@ -0,0 +1,200 @@
+use std::error::Error;
+use std::fmt;
+
+/// A run of consecutive non-blank lines, like a passport in day 4 or a group of people in
+/// day 6.
+#[derive(Debug)]
+pub struct Record<'a> {
+    /// The line in the input the record starts on.
+    pub line: usize,
+    pub lines: Vec<&'a str>,
+}
+
+#[derive(Debug)]
+pub struct RecordError {
+    pub line: usize,
+    pub column: usize,
+    pub message: String,
+}
+
+impl fmt::Display for RecordError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "line {}, column {}: {}",
+            self.line, self.column, self.message
+        )
+    }
+}
+
+impl Error for RecordError {}
+
+/// Like `FromStr`, but for a whole record, so that errors can point at the line and column
+/// in the input they come from.
+pub trait FromRecord: Sized {
+    fn from_record(record: &Record) -> Result<Self, RecordError>;
+}
+
+impl<'a> Record<'a> {
+    /// Every whitespace separated word in the record, with its line and column.
+    pub fn words(&self) -> Vec<(usize, usize, &'a str)> {
+        let mut words = Vec::new();
+        for (i, &line) in self.lines.iter().enumerate() {
+            let mut start = None;
+            for (pos, c) in line.char_indices().chain(Some((line.len(), ' '))) {
+                match (start, c.is_whitespace()) {
+                    (None, false) => start = Some(pos),
+                    (Some(s), true) => {
+                        let column = line[..s].chars().count() + 1;
+                        words.push((self.line + i, column, &line[s..pos]));
+                        start = None;
+                    }
+                    _ => {}
+                }
+            }
+        }
+        words
+    }
+
+    /// The words of the record split into `key:value` pairs, with the line and column of each.
+    pub fn key_values(&self) -> Result<Vec<(usize, usize, &'a str, &'a str)>, RecordError> {
+        self.words()
+            .into_iter()
+            .map(|(line, column, word)| match word.split_once(':') {
+                Some((key, value)) if !key.is_empty() && !value.is_empty() => {
+                    Ok((line, column, key, value))
+                }
+                _ => Err(RecordError {
+                    line,
+                    column,
+                    message: format!("expected key:value, found {:?}", word),
+                }),
+            })
+            .collect()
+    }
+
+    pub fn parse<T: FromRecord>(&self) -> Result<T, RecordError> {
+        T::from_record(self)
+    }
+}
+
+/// Splits `lines` into records separated by one or more blank lines.
+pub fn read_records(lines: &[String]) -> Vec<Record<'_>> {
+    let mut records = Vec::new();
+    let mut cur: Option<Record> = None;
+    for (i, line) in lines.iter().enumerate() {
+        if line.trim().is_empty() {
+            records.extend(cur.take());
+            continue;
+        }
+        cur.get_or_insert_with(|| Record {
+            line: i + 1,
+            lines: Vec::new(),
+        })
+        .lines
+        .push(line);
+    }
+    records.extend(cur);
+    records
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use proptest::prelude::*;
+
+    fn lines(text: &str) -> Vec<String> {
+        text.lines().map(String::from).collect()
+    }
+
+    fn key_value_error(text: &str) -> RecordError {
+        let lines = lines(text);
+        let records = read_records(&lines);
+        records
+            .iter()
+            .find_map(|record| record.key_values().err())
+            .expect("expected a malformed field")
+    }
+
+    #[test]
+    fn records_start_on_their_first_line() {
+        let lines = lines("a\nb\n\n\n  \nc");
+        let records = read_records(&lines);
+        assert_eq!(records.len(), 2);
+        assert_eq!(
+            (records[0].line, records[0].lines.clone()),
+            (1, vec!["a", "b"])
+        );
+        assert_eq!((records[1].line, records[1].lines.clone()), (6, vec!["c"]));
+    }
+
+    #[test]
+    fn key_values_point_at_each_field() {
+        let lines = lines("byr:1937 \t iyr:2017\n  hgt:183cm");
+        let records = read_records(&lines);
+        let fields = records[0].key_values().unwrap();
+        assert_eq!(
+            fields,
+            vec![
+                (1, 1, "byr", "1937"),
+                (1, 12, "iyr", "2017"),
+                (2, 3, "hgt", "183cm"),
+            ]
+        );
+    }
+
+    #[test]
+    fn columns_count_characters_not_bytes() {
+        let lines = lines("hcl:é日本 ecl:blu");
+        let records = read_records(&lines);
+        let fields = records[0].key_values().unwrap();
+        assert_eq!(fields[1], (1, 9, "ecl", "blu"));
+    }
+
+    #[test]
+    fn values_may_contain_colons() {
+        let lines = lines("cid:1:2");
+        let records = read_records(&lines);
+        assert_eq!(records[0].key_values().unwrap(), vec![(1, 1, "cid", "1:2")]);
+    }
+
+    #[test]
+    fn malformed_fields_are_errors() {
+        for (text, line, column) in [
+            ("byr", 1, 1),
+            ("byr:1920 hgt", 1, 10),
+            ("byr:1920\n  :5", 2, 3),
+            ("byr:", 1, 1),
+            (":", 1, 1),
+            ("é:1 ü", 1, 5),
+            ("a:1\n\nb:2\n\n\nc", 6, 1),
+        ] {
+            let err = key_value_error(text);
+            assert_eq!((err.line, err.column), (line, column), "{:?}", text);
+            assert!(err.message.starts_with("expected key:value"), "{:?}", text);
+        }
+    }
+
+    proptest! {
+        #[test]
+        fn reading_arbitrary_text_never_panics(text in any::<String>()) {
+            let lines = lines(&text);
+            for record in read_records(&lines) {
+                let _ = record.key_values();
+            }
+        }
+
+        #[test]
+        fn reading_field_like_text_never_panics(
+            text in "((byr|é|:|x)?(:[0-9a-z日]{0,3})?[ \t\n]{1,2}){0,10}"
+        ) {
+            let lines = lines(&text);
+            for record in read_records(&lines) {
+                if let Err(err) = record.key_values() {
+                    prop_assert!(err.line >= record.line);
+                    prop_assert!(err.column >= 1);
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,221 @@
+use std::collections::BTreeMap;
+use std::error::Error;
+
+/// A seat by its position on the plane, counting rows from the front and columns from the
+/// left, both from 0.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct Seat {
+    pub row: u32,
+    pub col: u32,
+}
+
+/// Boarding passes are binary space partitions: each row letter halves the rows and each
+/// column letter halves the columns. With the back half and the right half as 1s, a pass is
+/// just its seat ID written in binary, rows first.
+#[derive(Copy, Clone, Debug)]
+pub struct SeatCodec {
+    row_bits: u32,
+    col_bits: u32,
+}
+
+impl SeatCodec {
+    pub fn new(num_rows: u32, num_cols: u32) -> Result<SeatCodec, Box<dyn Error>> {
+        for (what, n) in &[("rows", num_rows), ("columns", num_cols)] {
+            if !n.is_power_of_two() {
+                return Err(format!("number of {} must be a power of two, got {}", what, n).into());
+            }
+        }
+        let codec = SeatCodec {
+            row_bits: num_rows.trailing_zeros(),
+            col_bits: num_cols.trailing_zeros(),
+        };
+        if codec.row_bits + codec.col_bits >= 32 {
+            return Err(format!("a {}x{} plane is too big", num_rows, num_cols).into());
+        }
+        Ok(codec)
+    }
+
+    pub fn num_rows(&self) -> u32 {
+        1 << self.row_bits
+    }
+
+    pub fn num_cols(&self) -> u32 {
+        1 << self.col_bits
+    }
+
+    pub fn pass_len(&self) -> usize {
+        (self.row_bits + self.col_bits) as usize
+    }
+
+    pub fn seat_id(&self, seat: Seat) -> u32 {
+        seat.row << self.col_bits | seat.col
+    }
+
+    pub fn seat(&self, seat_id: u32) -> Seat {
+        Seat {
+            row: seat_id >> self.col_bits,
+            col: seat_id & (self.num_cols() - 1),
+        }
+    }
+
+    pub fn decode(&self, pass: &str) -> Result<Seat, Box<dyn Error>> {
+        if pass.chars().count() != self.pass_len() {
+            return Err(format!(
+                "boarding pass {:?} should be {} characters long",
+                pass,
+                self.pass_len()
+            )
+            .into());
+        }
+
+        let mut seat_id = 0;
+        for (i, c) in pass.chars().enumerate() {
+            let (lower, upper) = if i < self.row_bits as usize {
+                ('F', 'B')
+            } else {
+                ('L', 'R')
+            };
+            let bit = match c {
+                c if c == lower => 0,
+                c if c == upper => 1,
+                _ => {
+                    return Err(format!(
+                        "boarding pass {:?} has {:?} at position {} instead of {} or {}",
+                        pass,
+                        c,
+                        i + 1,
+                        lower,
+                        upper
+                    )
+                    .into())
+                }
+            };
+            seat_id = seat_id << 1 | bit;
+        }
+        Ok(self.seat(seat_id))
+    }
+
+    pub fn encode(&self, seat_id: u32) -> Result<String, Box<dyn Error>> {
+        let num_seats = self.num_rows() * self.num_cols();
+        if seat_id >= num_seats {
+            return Err(
+                format!("seat {} is past the last seat, {}", seat_id, num_seats - 1).into(),
+            );
+        }
+
+        let mut pass = String::with_capacity(self.pass_len());
+        for i in 0..self.pass_len() {
+            let bit = seat_id >> (self.pass_len() - 1 - i) & 1;
+            pass.push(match (i < self.row_bits as usize, bit) {
+                (true, 0) => 'F',
+                (true, _) => 'B',
+                (false, 0) => 'L',
+                (false, _) => 'R',
+            });
+        }
+        Ok(pass)
+    }
+}
+
+/// The same seat on more than one boarding pass, with the lines (from 1) they came from.
+#[derive(Debug)]
+pub struct DuplicatePass {
+    pub seat_id: u32,
+    pub lines: Vec<usize>,
+}
+
+/// Which seats on the plane have a boarding pass.
+pub struct SeatMap {
+    codec: SeatCodec,
+    /// The line each taken seat's first boarding pass was on, by seat ID. Only taken seats are
+    /// kept, so a huge plane with few passes stays small.
+    taken_on: BTreeMap<u32, usize>,
+    duplicates: Vec<DuplicatePass>,
+}
+
+impl SeatMap {
+    pub fn new(codec: SeatCodec, passes: &[String]) -> Result<SeatMap, Box<dyn Error>> {
+        let mut taken_on = BTreeMap::new();
+        let mut duplicates: Vec<DuplicatePass> = Vec::new();
+
+        for (i, pass) in passes.iter().enumerate() {
+            let line = i + 1;
+            let seat_id = codec
+                .decode(pass)
+                .map(|seat| codec.seat_id(seat))
+                .map_err(|e| format!("line {}: {}", line, e))?;
+            match taken_on.get(&seat_id) {
+                None => {
+                    taken_on.insert(seat_id, line);
+                }
+                Some(&first) => match duplicates.iter_mut().find(|d| d.seat_id == seat_id) {
+                    Some(duplicate) => duplicate.lines.push(line),
+                    None => duplicates.push(DuplicatePass {
+                        seat_id,
+                        lines: vec![first, line],
+                    }),
+                },
+            }
+        }
+
+        Ok(SeatMap {
+            codec,
+            taken_on,
+            duplicates,
+        })
+    }
+
+    pub fn is_taken(&self, seat_id: u32) -> bool {
+        self.taken_on.contains_key(&seat_id)
+    }
+
+    pub fn max_seat_id(&self) -> Option<u32> {
+        self.taken_on.keys().next_back().copied()
+    }
+
+    pub fn duplicates(&self) -> &[DuplicatePass] {
+        &self.duplicates
+    }
+
+    /// Every run of seats without a boarding pass, as first and last seat ID.
+    pub fn free_ranges(&self) -> Vec<(u32, u32)> {
+        let last_seat = self.codec.num_rows() * self.codec.num_cols() - 1;
+        let mut ranges = Vec::new();
+        let mut next_free = Some(0);
+        for &id in self.taken_on.keys() {
+            if let Some(free) = next_free.filter(|&free| free < id) {
+                ranges.push((free, id - 1));
+            }
+            next_free = id.checked_add(1);
+        }
+        if let Some(free) = next_free.filter(|&free| free <= last_seat) {
+            ranges.push((free, last_seat));
+        }
+        ranges
+    }
+
+    /// Free seats whose IDs on either side are both taken, which is where our seat has to be.
+    pub fn candidate_seats(&self) -> Vec<u32> {
+        let taken: Vec<u32> = self.taken_on.keys().copied().collect();
+        taken
+            .windows(2)
+            .filter(|pair| pair[1] - pair[0] == 2)
+            .map(|pair| pair[0] + 1)
+            .collect()
+    }
+
+    /// Draws the plane front to back, one row per line, with `#` for taken seats and `.` for
+    /// free ones.
+    pub fn render(&self) -> String {
+        let mut map = String::new();
+        for row in 0..self.codec.num_rows() {
+            map.push_str(&format!("{:>4} ", row));
+            for col in 0..self.codec.num_cols() {
+                let id = self.codec.seat_id(Seat { row, col });
+                map.push(if self.is_taken(id) { '#' } else { '.' });
+            }
+            map.push('\n');
+        }
+        map
+    }
+}
@@ -0,0 +1,191 @@
+use std::collections::{BTreeSet, HashSet};
+use std::error::Error;
+use std::fs;
+use std::str::FromStr;
+
+use serde::{Deserialize, Serialize};
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Operation {
+    Acc,
+    Jmp,
+    Nop,
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Instruction {
+    pub op: Operation,
+    pub arg: i32,
+}
+
+impl FromStr for Instruction {
+    type Err = Box<dyn Error>;
+
+    fn from_str(line: &str) -> Result<Self, Self::Err> {
+        let parts: Vec<&str> = line.split_ascii_whitespace().collect();
+        if parts.len() != 2 {
+            return Err(format!("malformed instruction: {:?}", line).into());
+        }
+        let op = match parts[0] {
+            "acc" => Operation::Acc,
+            "jmp" => Operation::Jmp,
+            "nop" => Operation::Nop,
+            other => return Err(format!("unknown operation: {:?}", other).into()),
+        };
+        Ok(Instruction {
+            op,
+            arg: parts[1].parse()?,
+        })
+    }
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Status {
+    Running,
+    /// The next instruction has already been executed once.
+    Looped,
+    /// The instruction pointer left the program.
+    Terminated,
+}
+
+/// Everything needed to put a `Machine` back exactly where it was, minus the program itself.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Snapshot {
+    pub ip: i32,
+    pub acc: i32,
+    pub visited: BTreeSet<i32>,
+    pub steps: usize,
+}
+
+/// What gets written to disk: the (possibly patched) program along with the state, so a
+/// replay does not depend on the input file still matching.
+#[derive(Serialize, Deserialize)]
+struct SavedMachine {
+    program: Vec<Instruction>,
+    snapshot: Snapshot,
+}
+
+#[derive(Clone, Debug)]
+pub struct Machine {
+    program: Vec<Instruction>,
+    ip: i32,
+    acc: i32,
+    visited: HashSet<i32>,
+    steps: usize,
+}
+
+impl Machine {
+    pub fn new(program: &[Instruction]) -> Machine {
+        Machine {
+            program: program.to_vec(),
+            ip: 0,
+            acc: 0,
+            visited: HashSet::new(),
+            steps: 0,
+        }
+    }
+
+    pub fn ip(&self) -> i32 {
+        self.ip
+    }
+
+    pub fn acc(&self) -> i32 {
+        self.acc
+    }
+
+    pub fn steps(&self) -> usize {
+        self.steps
+    }
+
+    pub fn status(&self) -> Status {
+        if self.ip < 0 || self.ip >= self.program.len() as i32 {
+            Status::Terminated
+        } else if self.visited.contains(&self.ip) {
+            Status::Looped
+        } else {
+            Status::Running
+        }
+    }
+
+    /// Executes a single instruction, unless the machine has already stopped.
+    pub fn step(&mut self) -> Status {
+        let status = self.status();
+        if status != Status::Running {
+            return status;
+        }
+
+        self.visited.insert(self.ip);
+        let inst = self.program[self.ip as usize];
+        match inst.op {
+            Operation::Acc => {
+                self.acc += inst.arg;
+                self.ip += 1;
+            }
+            Operation::Jmp => self.ip += inst.arg,
+            Operation::Nop => self.ip += 1,
+        }
+        self.steps += 1;
+        self.status()
+    }
+
+    pub fn run(&mut self) -> Status {
+        let mut status = self.status();
+        while status == Status::Running {
+            status = self.step();
+        }
+        status
+    }
+
+    /// Runs at most `steps` instructions.
+    pub fn run_for(&mut self, steps: usize) -> Status {
+        let mut status = self.status();
+        for _ in 0..steps {
+            if status != Status::Running {
+                break;
+            }
+            status = self.step();
+        }
+        status
+    }
+
+    pub fn snapshot(&self) -> Snapshot {
+        Snapshot {
+            ip: self.ip,
+            acc: self.acc,
+            visited: self.visited.iter().copied().collect(),
+            steps: self.steps,
+        }
+    }
+
+    pub fn restore(&mut self, snapshot: &Snapshot) {
+        self.ip = snapshot.ip;
+        self.acc = snapshot.acc;
+        self.visited = snapshot.visited.iter().copied().collect();
+        self.steps = snapshot.steps;
+    }
+
+    /// An independent copy of this machine, free to be patched and run without affecting it.
+    pub fn fork(&self) -> Machine {
+        self.clone()
+    }
+
+    pub fn patch(&mut self, line: usize, inst: Instruction) {
+        self.program[line] = inst;
+    }
+
+    pub fn save(&self, path: &str) -> Result<(), Box<dyn Error>> {
+        let saved = SavedMachine {
+            program: self.program.clone(),
+            snapshot: self.snapshot(),
+        };
+        fs::write(path, serde_json::to_string_pretty(&saved)?)?;
+        Ok(())
+    }
+
+    pub fn load(path: &str) -> Result<Machine, Box<dyn Error>> {
+        let saved: SavedMachine = serde_json::from_str(&fs::read_to_string(path)?)?;
+        let mut machine = Machine::new(&saved.program);
+        machine.restore(&saved.snapshot);
+        Ok(machine)
+    }
+}
@@ -0,0 +1,158 @@
+use std::collections::{HashMap, VecDeque};
+use std::ops::Range;
+
+/// Checks an XMAS stream one number at a time. The last `preamble` numbers are kept both in
+/// arrival order, to know which one to evict, and as a multiset of counts. Adding and evicting
+/// a number are O(1), but a check is O(preamble): it looks up one complement per distinct
+/// number in the window. Keeping every pairwise sum instead would make checks O(1) at the cost
+/// of O(preamble) updates and O(preamble²) memory.
+pub struct XmasValidator {
+    preamble: usize,
+    window: VecDeque<i64>,
+    counts: HashMap<i64, usize>,
+    position: usize,
+}
+
+impl XmasValidator {
+    pub fn new(preamble: usize) -> XmasValidator {
+        XmasValidator {
+            preamble,
+            window: VecDeque::with_capacity(preamble + 1),
+            counts: HashMap::new(),
+            position: 0,
+        }
+    }
+
+    /// Whether two different numbers in the current window add up to `n`, in O(preamble).
+    pub fn is_valid(&self, n: i64) -> bool {
+        self.counts.keys().any(|&v| match n.checked_sub(v) {
+            Some(comp) => comp != v && self.counts.contains_key(&comp),
+            None => false,
+        })
+    }
+
+    /// Feeds the next number of the stream. Returns whether it was valid, or `None` while the
+    /// preamble is still filling up and there is nothing to check against.
+    pub fn push(&mut self, n: i64) -> Option<bool> {
+        let valid = if self.window.len() < self.preamble {
+            None
+        } else {
+            Some(self.is_valid(n))
+        };
+
+        self.window.push_back(n);
+        *self.counts.entry(n).or_default() += 1;
+        if self.window.len() > self.preamble {
+            let evicted = self.window.pop_front().unwrap();
+            let count = self.counts.get_mut(&evicted).unwrap();
+            *count -= 1;
+            if *count == 0 {
+                self.counts.remove(&evicted);
+            }
+        }
+        self.position += 1;
+
+        valid
+    }
+
+    /// How many numbers have been pushed so far.
+    pub fn position(&self) -> usize {
+        self.position
+    }
+}
+
+/// Yields the position (counting from 0) and value of every number in `numbers` that isn't
+/// the sum of two different numbers among the `preamble` before it.
+pub fn invalid_numbers<I>(numbers: I, preamble: usize) -> InvalidNumbers<I::IntoIter>
+where
+    I: IntoIterator<Item = i64>,
+{
+    InvalidNumbers {
+        numbers: numbers.into_iter(),
+        validator: XmasValidator::new(preamble),
+    }
+}
+
+pub struct InvalidNumbers<I> {
+    numbers: I,
+    validator: XmasValidator,
+}
+
+impl<I: Iterator<Item = i64>> Iterator for InvalidNumbers<I> {
+    type Item = (usize, i64);
+
+    fn next(&mut self) -> Option<(usize, i64)> {
+        for n in &mut self.numbers {
+            let position = self.validator.position();
+            if self.validator.push(n) == Some(false) {
+                return Some((position, n));
+            }
+        }
+        None
+    }
+}
+
+/// The first run of at least two consecutive numbers adding up to `target`, in the sense of
+/// the one that ends earliest.
+///
+/// With no negative numbers in the way, growing the window on the right and shrinking it on
+/// the left finds it in a single pass. Otherwise a window that's too big might still come
+/// down to the target, so instead each prefix sum is looked up against the earliest prefix
+/// sum it could start after.
+pub fn find_contiguous_range(data: &[i64], target: i64) -> Option<Range<usize>> {
+    if data.iter().all(|&n| n >= 0) {
+        let target = target as i128;
+        let (mut start, mut sum) = (0, 0i128);
+        for (end, &n) in data.iter().enumerate() {
+            sum += n as i128;
+            while sum > target && start < end {
+                sum -= data[start] as i128;
+                start += 1;
+            }
+            if sum == target && end > start {
+                return Some(start..end + 1);
+            }
+        }
+        None
+    } else {
+        let prefix = prefix_sums(data);
+        let mut earliest: HashMap<i128, usize> = HashMap::new();
+        for end in 2..prefix.len() {
+            earliest.entry(prefix[end - 2]).or_insert(end - 2);
+            if let Some(&start) = earliest.get(&(prefix[end] - target as i128)) {
+                return Some(start..end);
+            }
+        }
+        None
+    }
+}
+
+/// Every run of at least two consecutive numbers adding up to `target`, ordered by where they
+/// end and then by where they start.
+pub fn contiguous_ranges(data: &[i64], target: i64) -> Vec<Range<usize>> {
+    let prefix = prefix_sums(data);
+    let mut starts: HashMap<i128, Vec<usize>> = HashMap::new();
+    let mut ranges = Vec::new();
+    for end in 2..prefix.len() {
+        starts.entry(prefix[end - 2]).or_default().push(end - 2);
+        if let Some(found) = starts.get(&(prefix[end] - target as i128)) {
+            ranges.extend(found.iter().map(|&start| start..end));
+        }
+    }
+    ranges
+}
+
+/// `prefix[i]` is the sum of the first `i` numbers, widened so that it can't overflow.
+fn prefix_sums(data: &[i64]) -> Vec<i128> {
+    let mut prefix = Vec::with_capacity(data.len() + 1);
+    prefix.push(0);
+    for &n in data {
+        prefix.push(prefix.last().unwrap() + n as i128);
+    }
+    prefix
+}
+
+/// The XMAS weakness of a range: its smallest number plus its largest.
+pub fn weakness(block: &[i64]) -> Option<i128> {
+    Some(*block.iter().min()? as i128 + *block.iter().max()? as i128)
+}